@@ -0,0 +1,46 @@
+use std::collections::HashMap;
+
+use super::ty::{Class, Scheme, Ty};
+
+/// The types of the builtin functions, which a program can redefine.
+pub fn builtins() -> HashMap<&'static str, Scheme> {
+    let t = || Ty::Param("T".into());
+    let generic = |class, ty| Scheme {
+        params: vec![("T".into(), class)],
+        ty,
+    };
+    let func = |params, ret| Ty::Fn(params, Box::new(ret));
+    HashMap::from([
+        ("read_line", Scheme::mono(func(vec![], Ty::String))),
+        // of a string, an array or a range
+        ("len", Scheme::mono(func(vec![Ty::Unknown], Ty::Int))),
+        (
+            "push",
+            generic(
+                Class::Any,
+                func(vec![Ty::Array(Box::new(t())), t()], Ty::Unit),
+            ),
+        ),
+        (
+            "pop",
+            generic(Class::Any, func(vec![Ty::Array(Box::new(t()))], t())),
+        ),
+        ("abs", generic(Class::Num, func(vec![t()], t()))),
+        ("min", generic(Class::Num, func(vec![t(), t()], t()))),
+        ("max", generic(Class::Num, func(vec![t(), t()], t()))),
+        ("sqrt", generic(Class::Num, func(vec![t()], Ty::Float))),
+        ("floor", generic(Class::Num, func(vec![t()], Ty::Float))),
+        ("str", generic(Class::Any, func(vec![t()], Ty::String))),
+        ("int", generic(Class::Any, func(vec![t()], Ty::Int))),
+        ("float", generic(Class::Any, func(vec![t()], Ty::Float))),
+    ])
+}
+
+/// What the builtins taking any number of arguments return.
+pub fn variadic(name: &str) -> Option<Ty> {
+    match name {
+        "print" | "println" => Some(Ty::Unit),
+        "concat" | "format" => Some(Ty::String),
+        _ => None,
+    }
+}
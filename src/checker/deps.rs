@@ -0,0 +1,151 @@
+use std::collections::HashSet;
+
+use crate::parser::*;
+
+/// Collects the names `expr` refers to, whether they are locals or globals.
+pub fn references<'a>(expr: &Expr<'a>, names: &mut HashSet<&'a str>) {
+    match &expr.kind {
+        ExprKind::Id(ident) => {
+            names.insert(ident.0);
+        }
+        ExprKind::Literal(_) | ExprKind::Path(..) => {}
+        ExprKind::BinOp(lhs, _, rhs) => {
+            references(lhs, names);
+            references(rhs, names);
+        }
+        ExprKind::Prefix(_, expr)
+        | ExprKind::TupleField { expr, .. }
+        | ExprKind::Field { expr, .. } => references(expr, names),
+        ExprKind::Fn(function) => statements(&function.body, names),
+        ExprKind::Call { func, args } => {
+            references(func, names);
+            args.iter().for_each(|arg| references(arg, names));
+        }
+        ExprKind::If { cond, then, els } => {
+            references(cond, names);
+            block(then, names);
+            if let Some(els) = els {
+                block(els, names);
+            }
+        }
+        ExprKind::Index { expr, index } => {
+            references(expr, names);
+            references(index, names);
+        }
+        ExprKind::Slice { expr, start, end } => {
+            references(expr, names);
+            for bound in [start, end].into_iter().flatten() {
+                references(bound, names);
+            }
+        }
+        ExprKind::Tuple(elements) | ExprKind::Array(elements) => {
+            elements.iter().for_each(|e| references(e, names));
+        }
+        ExprKind::Struct { fields, .. } => {
+            fields
+                .iter()
+                .for_each(|(_, value)| references(value, names));
+        }
+        ExprKind::Match { expr, arms } => {
+            references(expr, names);
+            arms.iter().for_each(|arm| block(&arm.body, names));
+        }
+    }
+}
+
+fn block<'a>(block: &Block<'a>, names: &mut HashSet<&'a str>) {
+    statements(&block.body, names);
+    if let Some(value) = &block.value {
+        references(value, names);
+    }
+}
+
+fn statements<'a>(body: &[Statement<'a>], names: &mut HashSet<&'a str>) {
+    for statement in body {
+        match &statement.kind {
+            StatementKind::Let(_, _, expr)
+            | StatementKind::Return(expr)
+            | StatementKind::Expr(expr) => references(expr, names),
+            StatementKind::Set(ident, value) => {
+                names.insert(ident.0);
+                references(value, names);
+            }
+            StatementKind::SetIndex {
+                target,
+                index,
+                value,
+            } => {
+                references(target, names);
+                references(index, names);
+                references(value, names);
+            }
+            StatementKind::SetField { target, value, .. } => {
+                references(target, names);
+                references(value, names);
+            }
+            StatementKind::While { cond, body } => {
+                references(cond, names);
+                block(body, names);
+            }
+            StatementKind::For { iterable, body, .. } => {
+                references(iterable, names);
+                block(body, names);
+            }
+            StatementKind::Break | StatementKind::Continue => {}
+        }
+    }
+}
+
+/// Groups the nodes of a graph into its strongly connected components, each
+/// after the components it has edges to. `edges[v]` are the nodes `v` has edges to.
+pub fn components(edges: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    const UNSEEN: usize = usize::MAX;
+    let mut index = vec![UNSEEN; edges.len()];
+    let mut low = vec![0; edges.len()];
+    let mut on_stack = vec![false; edges.len()];
+    let mut stack = Vec::new();
+    let mut components = Vec::new();
+    let mut next = 0;
+    // Tarjan's algorithm, with the recursion kept in `work`: each node with
+    // the next of its edges to follow
+    for root in 0..edges.len() {
+        if index[root] != UNSEEN {
+            continue;
+        }
+        let mut work = vec![(root, 0)];
+        while let Some((v, i)) = work.pop() {
+            if i == 0 {
+                index[v] = next;
+                low[v] = next;
+                next += 1;
+                stack.push(v);
+                on_stack[v] = true;
+            }
+            if let Some(&w) = edges[v].get(i) {
+                work.push((v, i + 1));
+                if index[w] == UNSEEN {
+                    work.push((w, 0));
+                } else if on_stack[w] {
+                    low[v] = low[v].min(index[w]);
+                }
+                continue;
+            }
+            if low[v] == index[v] {
+                let mut component = Vec::new();
+                while let Some(w) = stack.pop() {
+                    on_stack[w] = false;
+                    component.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                component.sort();
+                components.push(component);
+            }
+            if let Some(&(parent, _)) = work.last() {
+                low[parent] = low[parent].min(low[v]);
+            }
+        }
+    }
+    components
+}
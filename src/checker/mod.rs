@@ -0,0 +1,1329 @@
+mod builtins;
+mod deps;
+mod ty;
+mod unify;
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    rc::Rc,
+};
+
+use crate::parser::*;
+use builtins::*;
+use unify::{Fail, Unifier};
+
+pub use deps::references;
+pub use ty::{Class, Scheme, Ty};
+
+/// A value whose type does not fit where it is used, found before running.
+#[derive(Debug)]
+pub struct TypeError {
+    pub msg: String,
+    pub start: Position,
+    pub end: Position,
+    /// The source line where the error starts, for showing a snippet.
+    pub line: String,
+    /// Where the type it conflicts with comes from, when it is elsewhere.
+    pub note: Option<Box<TypeError>>,
+}
+
+impl TypeError {
+    pub fn new(msg: String, source: &str, span: Span) -> Self {
+        let start = Position::of(source, span.start);
+        TypeError {
+            msg,
+            start,
+            end: Position::of(source, span.end),
+            line: source_line(source, start.line).to_string(),
+            note: None,
+        }
+    }
+
+    /// Renders the error with a caret-underlined snippet of the source,
+    /// pointing at `path` when the source comes from a file.
+    pub fn render(&self, path: Option<&str>) -> String {
+        let snippet = render_snippet(self.start, self.end, &self.line, path);
+        let mut out = format!("error: {}\n{}", self.msg, snippet);
+        if let Some(note) = &self.note {
+            let snippet = render_snippet(note.start, note.end, &note.line, path);
+            out += &format!("\nnote: {}\n{}", note.msg, snippet);
+        }
+        out
+    }
+}
+
+impl Display for TypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.render(None))
+    }
+}
+
+impl std::error::Error for TypeError {}
+
+/// A type error, before its spans are placed in the source.
+struct Error {
+    msg: String,
+    span: Span,
+    /// Where the other type comes from.
+    note: Option<Span>,
+}
+
+impl Error {
+    fn new(msg: String, span: Span) -> Self {
+        Error {
+            msg,
+            span,
+            note: None,
+        }
+    }
+
+    fn place(self, source: &str) -> TypeError {
+        let note = (self.note).map(|span| {
+            let msg = "the other type comes from here".to_string();
+            Box::new(TypeError::new(msg, source, span))
+        });
+        TypeError {
+            note,
+            ..TypeError::new(self.msg, source, self.span)
+        }
+    }
+}
+
+type Check<T> = Result<T, Error>;
+
+/// Infers the type of every value of a program, checking it against its
+/// annotations, and returns the type of each global in order. Functions are
+/// generic over what they leave unconstrained, and what is only known at
+/// run time, like the fields of a struct, is of type `any`.
+pub fn check_program(program: &Program, source: &str) -> Result<Vec<(String, String)>, TypeError> {
+    let mut env = TypeEnv::default();
+    check_module(program, source, &mut env)
+}
+
+/// Checks a program as `check_program` does, against the declarations of
+/// the ones checked before with `env`, adding its own to it.
+pub fn check_module(
+    program: &Program,
+    source: &str,
+    env: &mut TypeEnv,
+) -> Result<Vec<(String, String)>, TypeError> {
+    let mut checker = Checker::new(std::mem::take(env));
+    let result = (checker.items(&program.0)).map(|_| checker.types(program));
+    *env = checker.into_env();
+    result.map_err(|e| e.place(source))
+}
+
+/// Checks a REPL line against the declarations of the lines before it,
+/// adding its own to `env`.
+pub fn check_line(line: &Line, source: &str, env: &mut TypeEnv) -> Result<(), TypeError> {
+    let mut checker = Checker::new(std::mem::take(env));
+    let result = match line {
+        Line::Item(item) => checker.items(std::slice::from_ref(item)),
+        Line::Expr(expr) => checker.expr(expr).map(|_| ()),
+    };
+    *env = checker.into_env();
+    result.map_err(|e| e.place(source))
+}
+
+/// The declarations of the sources checked so far, which the REPL and the
+/// engine keep to check a line or a source against the ones before it.
+#[derive(Debug, Default, Clone)]
+pub struct TypeEnv {
+    unifier: Unifier,
+    structs: HashMap<Rc<str>, StructDef>,
+    enums: HashSet<Rc<str>>,
+    globals: HashMap<Rc<str>, Scheme>,
+}
+
+impl TypeEnv {
+    /// Makes the type of the global `name` unknown, for a value the host defines.
+    pub fn forget(&mut self, name: &str) {
+        self.globals.insert(name.into(), Scheme::mono(Ty::Unknown));
+    }
+}
+
+/// A declared struct, with the type of each of its fields in terms of its
+/// type parameters. A field without a type is of type `any`.
+#[derive(Debug, Clone)]
+struct StructDef {
+    params: Vec<Rc<str>>,
+    fields: Fields,
+}
+
+/// The fields of a struct by name, with their types.
+type Fields = Vec<(Rc<str>, Ty)>;
+
+struct Checker<'a> {
+    unifier: Unifier,
+    structs: HashMap<Rc<str>, StructDef>,
+    enums: HashSet<Rc<str>>,
+    globals: HashMap<Rc<str>, Scheme>,
+    builtins: HashMap<&'static str, Scheme>,
+    /// The locals of the enclosing blocks, innermost last. Only a function
+    /// declared with type parameters is generic.
+    scopes: Vec<HashMap<&'a str, Scheme>>,
+    /// The type parameters of the enclosing functions or struct.
+    generics: Vec<&'a str>,
+    /// What each enclosing function returns, innermost last, with the
+    /// annotation saying so, if any.
+    returns: Vec<(Ty, Option<Span>)>,
+}
+
+impl<'a> Checker<'a> {
+    fn new(env: TypeEnv) -> Self {
+        Checker {
+            unifier: env.unifier,
+            structs: env.structs,
+            enums: env.enums,
+            globals: env.globals,
+            builtins: builtins(),
+            scopes: Vec::new(),
+            generics: Vec::new(),
+            returns: Vec::new(),
+        }
+    }
+
+    fn into_env(self) -> TypeEnv {
+        TypeEnv {
+            unifier: self.unifier,
+            structs: self.structs,
+            enums: self.enums,
+            globals: self.globals,
+        }
+    }
+
+    fn items(&mut self, program: &'a [Item<'a>]) -> Check<()> {
+        let mut items = Vec::new();
+        let mut structs = Vec::new();
+        for item in program {
+            match &item.kind {
+                ItemKind::Struct {
+                    ident,
+                    generics,
+                    fields,
+                } => {
+                    let params = generics.iter().map(|g| g.0.into()).collect();
+                    let def = StructDef {
+                        params,
+                        fields: Vec::new(),
+                    };
+                    self.structs.insert(ident.0.into(), def);
+                    structs.push((ident.0, generics, fields));
+                }
+                ItemKind::Enum { ident, .. } => {
+                    self.enums.insert(ident.0.into());
+                }
+                ItemKind::DefItem { ident, ty, expr } => items.push((ident, ty, expr)),
+            }
+        }
+        // the fields are typed once every struct is known, for them to refer to one another
+        for (name, generics, fields) in structs {
+            self.generics.extend(generics.iter().map(|g| g.0));
+            let fields = (fields.iter())
+                .map(|Field { ident, ty }| match ty {
+                    Some(ty) => Ok((ident.0.into(), self.resolve(ty)?)),
+                    None => Ok((ident.0.into(), Ty::Unknown)),
+                })
+                .collect::<Check<_>>();
+            self.generics.clear();
+            self.structs.get_mut(name).unwrap().fields = fields?;
+        }
+        // a global is inferred after those it refers to, and together with
+        // those referring back to it
+        let index: HashMap<_, _> = (items.iter().enumerate())
+            .map(|(i, (ident, ..))| (ident.0, i))
+            .collect();
+        let edges: Vec<Vec<usize>> = (items.iter())
+            .map(|(_, _, expr)| {
+                let mut names = HashSet::new();
+                deps::references(expr, &mut names);
+                let mut edges: Vec<_> = names.iter().filter_map(|name| index.get(name)).collect();
+                edges.sort();
+                edges.into_iter().copied().collect()
+            })
+            .collect();
+        for component in deps::components(&edges) {
+            let mut expected = Vec::new();
+            for &i in &component {
+                let (ident, ty, expr) = items[i];
+                let ty = match (ty, &expr.kind) {
+                    (Some(ty), _) => self.resolve(ty)?,
+                    (None, ExprKind::Fn(function)) => self.signature(function)?,
+                    (None, _) => self.unifier.fresh(Class::Any),
+                };
+                self.globals
+                    .insert(ident.0.into(), Scheme::mono(ty.clone()));
+                expected.push(ty);
+            }
+            for (&i, expected) in component.iter().zip(&expected) {
+                let (_, ty, expr) = items[i];
+                let found = self.expr(expr)?;
+                let origin = ty.as_ref().map(|ty| ty.span);
+                self.unify(expected, &found, expr.span, origin)?;
+            }
+            // only functions are generic, as other values are made only once
+            let names: HashSet<_> = component.iter().map(|&i| items[i].0 .0).collect();
+            let env = self.env_vars(&names);
+            for (&i, ty) in component.iter().zip(&expected) {
+                let (ident, _, expr) = items[i];
+                if let ExprKind::Fn(function) = &expr.kind {
+                    let scheme = self.unifier.generalize(ty, &env, type_params(function));
+                    self.globals.insert(ident.0.into(), scheme);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The variables in the types of the globals other than `except`.
+    fn env_vars(&self, except: &HashSet<&str>) -> HashSet<usize> {
+        (self.globals.iter())
+            .filter(|(name, _)| !except.contains(&***name))
+            .flat_map(|(_, scheme)| self.unifier.free_vars(&scheme.ty))
+            .collect()
+    }
+
+    /// The type of each global, in order.
+    fn types(&self, program: &Program) -> Vec<(String, String)> {
+        (program.0.iter())
+            .filter_map(|item| match &item.kind {
+                ItemKind::DefItem { ident, .. } => {
+                    let scheme = &self.globals[ident.0];
+                    let scheme = Scheme {
+                        params: scheme.params.clone(),
+                        ty: self.unifier.resolve(&scheme.ty),
+                    };
+                    Some((ident.0.to_string(), scheme.to_string()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The type an annotation stands for.
+    fn resolve(&self, ty: &Type) -> Check<Ty> {
+        Ok(match &ty.kind {
+            TypeKind::Named(name, args) => {
+                let args: Vec<_> = args
+                    .iter()
+                    .map(|ty| self.resolve(ty))
+                    .collect::<Check<_>>()?;
+                let expected = match *name {
+                    _ if self.generics.contains(name) => 0,
+                    "Array" => 1,
+                    _ => self.structs.get(*name).map_or(0, |def| def.params.len()),
+                };
+                if args.len() != expected {
+                    let msg = format!(
+                        "`{}` takes {} type arguments, found {}",
+                        name,
+                        expected,
+                        args.len()
+                    );
+                    return Err(Error::new(msg, ty.span));
+                }
+                match *name {
+                    _ if self.generics.contains(name) => Ty::Param((*name).into()),
+                    "int" => Ty::Int,
+                    "float" => Ty::Float,
+                    "bool" => Ty::Bool,
+                    "string" => Ty::String,
+                    "range" => Ty::Range,
+                    "Array" => Ty::Array(Box::new(args[0].clone())),
+                    _ if self.structs.contains_key(*name) => Ty::Struct((*name).into(), args),
+                    _ if self.enums.contains(*name) => Ty::Enum((*name).into()),
+                    _ => return Err(Error::new(format!("unknown type `{}`", name), ty.span)),
+                }
+            }
+            TypeKind::Tuple(types) if types.is_empty() => Ty::Unit,
+            TypeKind::Tuple(types) => Ty::Tuple(
+                types
+                    .iter()
+                    .map(|ty| self.resolve(ty))
+                    .collect::<Check<_>>()?,
+            ),
+            TypeKind::Array(ty) => Ty::Array(Box::new(self.resolve(ty)?)),
+            TypeKind::Fn(params, ret) => {
+                let params = params
+                    .iter()
+                    .map(|ty| self.resolve(ty))
+                    .collect::<Check<_>>()?;
+                let ret = match ret {
+                    Some(ret) => self.resolve(ret)?,
+                    None => Ty::Unit,
+                };
+                Ty::Fn(params, Box::new(ret))
+            }
+        })
+    }
+
+    /// The type of a function as far as its annotations tell, with
+    /// variables for the rest.
+    fn signature(&mut self, function: &'a Function<'a>) -> Check<Ty> {
+        let outer = self.generics.len();
+        self.generics.extend(function.generics.iter().map(|g| g.0));
+        let params = (function.params.iter())
+            .map(|param| self.annotation(&param.ty))
+            .collect::<Check<_>>();
+        let ret = self.annotation(&function.ret);
+        self.generics.truncate(outer);
+        Ok(Ty::Fn(params?, Box::new(ret?)))
+    }
+
+    fn annotation(&mut self, ty: &Option<Type>) -> Check<Ty> {
+        match ty {
+            Some(ty) => self.resolve(ty),
+            None => Ok(self.unifier.fresh(Class::Any)),
+        }
+    }
+
+    fn function(&mut self, function: &'a Function<'a>, span: Span) -> Check<Ty> {
+        let outer = self.generics.len();
+        self.generics.extend(function.generics.iter().map(|g| g.0));
+        let result = self.function_body(function, span);
+        self.generics.truncate(outer);
+        result
+    }
+
+    /// Checks a function, with its type parameters in scope. Within its
+    /// body, they stand for any type, and so only match themselves.
+    fn function_body(&mut self, function: &'a Function<'a>, span: Span) -> Check<Ty> {
+        let mut params = Vec::new();
+        let mut scope = HashMap::new();
+        for Param { ident, ty } in &function.params {
+            let ty = self.annotation(ty)?;
+            scope.insert(ident.0, Scheme::mono(ty.clone()));
+            params.push(ty);
+        }
+        let ret = self.annotation(&function.ret)?;
+        let origin = function.ret.as_ref().map(|ty| ty.span);
+        self.scopes.push(scope);
+        self.returns.push((ret.clone(), origin));
+        let diverges = self.statements(&function.body);
+        self.scopes.pop();
+        self.returns.pop();
+        let diverges = diverges?;
+        // falling off the end of the body returns unit
+        if !diverges {
+            self.unify(&ret, &Ty::Unit, span, origin).map_err(|mut e| {
+                e.msg += " at the end of the function";
+                e
+            })?;
+        }
+        Ok(Ty::Fn(params, Box::new(ret)))
+    }
+
+    /// Checks statements in the innermost scope, telling whether they
+    /// always leave it early, through `return`, `break` or `continue`.
+    fn statements(&mut self, body: &'a [Statement<'a>]) -> Check<bool> {
+        let mut diverges = false;
+        for statement in body {
+            diverges |= self.statement(statement)?;
+        }
+        Ok(diverges)
+    }
+
+    fn block(&mut self, block: &'a Block<'a>) -> Check<Ty> {
+        self.scopes.push(HashMap::new());
+        let diverges = self.statements(&block.body)?;
+        let value = match &block.value {
+            Some(value) => self.expr(value)?,
+            None => Ty::Unit,
+        };
+        self.scopes.pop();
+        Ok(if diverges { Ty::Never } else { value })
+    }
+
+    /// Checks a statement, telling whether it always leaves its block.
+    fn statement(&mut self, statement: &'a Statement<'a>) -> Check<bool> {
+        match &statement.kind {
+            StatementKind::Let(pattern, ty, value) => {
+                let origin = ty.as_ref().map(|ty| ty.span);
+                let expected = match (ty, &value.kind) {
+                    (Some(ty), _) => self.resolve(ty)?,
+                    (None, ExprKind::Fn(function)) => self.signature(function)?,
+                    (None, _) => self.unifier.fresh(Class::Any),
+                };
+                // declared first, so that a function can call itself
+                if let (Pattern::Id(ident), ExprKind::Fn(_)) = (pattern, &value.kind) {
+                    self.declare(ident.0, expected.clone());
+                }
+                let found = self.expr(value)?;
+                self.unify(&expected, &found, value.span, origin)?;
+                match (pattern, &value.kind) {
+                    // generic over its type parameters only
+                    (Pattern::Id(ident), ExprKind::Fn(function))
+                        if !function.generics.is_empty() =>
+                    {
+                        let scheme = Scheme {
+                            params: type_params(function),
+                            ty: self.unifier.resolve(&expected),
+                        };
+                        self.scopes.last_mut().unwrap().insert(ident.0, scheme);
+                    }
+                    _ => self.bind(pattern, expected)?,
+                }
+            }
+            StatementKind::Set(ident, value) => {
+                let expected = self.lookup(ident.0);
+                let found = self.expr(value)?;
+                self.unify(&expected, &found, value.span, None)?;
+            }
+            StatementKind::SetIndex {
+                target,
+                index,
+                value,
+            } => {
+                let ty = self.expr(target)?;
+                self.expect(&Ty::Int, index)?;
+                match self.element(&ty, target.span)? {
+                    Some(element) => self.expect(&element, value)?,
+                    None => {
+                        let msg = format!("cannot assign to an element of `{}`", self.show(&ty));
+                        return Err(Error::new(msg, target.span));
+                    }
+                };
+            }
+            StatementKind::SetField {
+                target,
+                field,
+                value,
+            } => {
+                let ty = self.expr(target)?;
+                let expected = self.field(&ty, field)?;
+                self.expect(&expected, value)?;
+            }
+            StatementKind::Return(value) => {
+                let found = self.expr(value)?;
+                if let Some((ret, origin)) = self.returns.last().cloned() {
+                    self.unify(&ret, &found, value.span, origin)?;
+                }
+                return Ok(true);
+            }
+            StatementKind::Expr(expr) => return Ok(self.expr(expr)? == Ty::Never),
+            StatementKind::While { cond, body } => {
+                self.expect(&Ty::Bool, cond)?;
+                self.block(body)?;
+            }
+            StatementKind::For {
+                pattern,
+                iterable,
+                body,
+            } => {
+                let ty = self.expr(iterable)?;
+                let element = match self.shallow(&ty) {
+                    Ty::Range => Ty::Int,
+                    Ty::String => Ty::String,
+                    _ => match self.element(&ty, iterable.span)? {
+                        Some(element) => element,
+                        None => {
+                            let msg = format!("cannot iterate over `{}`", self.show(&ty));
+                            return Err(Error::new(msg, iterable.span));
+                        }
+                    },
+                };
+                self.scopes.push(HashMap::new());
+                self.bind(pattern, element)?;
+                self.block(body)?;
+                self.scopes.pop();
+            }
+            StatementKind::Break | StatementKind::Continue => return Ok(true),
+        }
+        Ok(false)
+    }
+
+    fn expr(&mut self, expr: &'a Expr<'a>) -> Check<Ty> {
+        let span = expr.span;
+        match &expr.kind {
+            ExprKind::Id(ident) => Ok(self.lookup(ident.0)),
+            ExprKind::Literal(literal) => Ok(literal_type(literal)),
+            ExprKind::BinOp(lhs, op, rhs) => {
+                let a = self.expr(lhs)?;
+                let b = self.expr(rhs)?;
+                match op {
+                    // numbers compare by value, whatever their type
+                    Infix::Eq | Infix::Ne => {
+                        if !(self.shallow(&a).is_number() && self.shallow(&b).is_number()) {
+                            self.unify(&a, &b, rhs.span, Some(lhs.span))?;
+                        }
+                        Ok(Ty::Bool)
+                    }
+                    Infix::And | Infix::Or => {
+                        self.unify(&Ty::Bool, &a, lhs.span, None)?;
+                        self.unify(&Ty::Bool, &b, rhs.span, None)?;
+                        Ok(Ty::Bool)
+                    }
+                    Infix::Range => {
+                        self.unify(&Ty::Int, &a, lhs.span, None)?;
+                        self.unify(&Ty::Int, &b, rhs.span, None)?;
+                        Ok(Ty::Range)
+                    }
+                    Infix::Lt | Infix::Le | Infix::Gt | Infix::Ge => {
+                        self.arith(op, Class::Ordered, (&a, lhs), (&b, rhs), span)?;
+                        Ok(Ty::Bool)
+                    }
+                    Infix::Add => self.arith(op, Class::Ordered, (&a, lhs), (&b, rhs), span),
+                    Infix::Sub | Infix::Mul | Infix::Div => {
+                        self.arith(op, Class::Num, (&a, lhs), (&b, rhs), span)
+                    }
+                }
+            }
+            ExprKind::Prefix(op, rhs) => {
+                let ty = self.expr(rhs)?;
+                let expected = match op {
+                    Prefix::Neg => self.unifier.fresh(Class::Num),
+                    Prefix::Not => Ty::Bool,
+                };
+                match self.unifier.unify(&expected, &ty, span) {
+                    Ok(()) => Ok(ty),
+                    Err(_) => {
+                        let msg = format!("cannot apply `{}` to `{}`", op, self.show(&ty));
+                        Err(Error::new(msg, span))
+                    }
+                }
+            }
+            ExprKind::Fn(function) => self.function(function, span),
+            // building a variant
+            ExprKind::Call { func, args } if matches!(func.kind, ExprKind::Path(..)) => {
+                for arg in args {
+                    self.expr(arg)?;
+                }
+                self.expr(func)
+            }
+            ExprKind::Call { func, args } => {
+                if let Some(ret) = self.variadic(func) {
+                    for arg in args {
+                        self.expr(arg)?;
+                    }
+                    return Ok(ret);
+                }
+                let ty = self.expr(func)?;
+                match self.shallow(&ty) {
+                    Ty::Fn(params, ret) => {
+                        if params.len() != args.len() {
+                            let msg = format!(
+                                "expected {} arguments, found {}",
+                                params.len(),
+                                args.len()
+                            );
+                            return Err(Error::new(msg, span));
+                        }
+                        // a number parameter given an int and a float is a
+                        // float, the VM promoting the int
+                        let numbers: Vec<_> = (params.iter())
+                            .map(|param| {
+                                matches!(
+                                    self.unifier.class(param),
+                                    Some(Class::Num | Class::Ordered)
+                                )
+                            })
+                            .collect();
+                        let found = args
+                            .iter()
+                            .map(|e| self.expr(e))
+                            .collect::<Check<Vec<_>>>()?;
+                        for ((param, found), number) in params.iter().zip(&found).zip(&numbers) {
+                            if *number && self.shallow(found) == Ty::Float {
+                                self.unifier.unify(param, &Ty::Float, span).ok();
+                            }
+                        }
+                        for (((param, found), arg), number) in
+                            params.iter().zip(&found).zip(args).zip(numbers)
+                        {
+                            let promoted =
+                                self.shallow(param) == Ty::Float && self.shallow(found) == Ty::Int;
+                            if !(number && promoted) {
+                                self.unify(param, found, arg.span, None)?;
+                            }
+                        }
+                        Ok(*ret)
+                    }
+                    // a function passed in, found from how it is called
+                    Ty::Var(_) => {
+                        let args = args.iter().map(|e| self.expr(e)).collect::<Check<_>>()?;
+                        let ret = self.unifier.fresh(Class::Any);
+                        let found = Ty::Fn(args, Box::new(ret.clone()));
+                        self.unify(&ty, &found, func.span, None)?;
+                        Ok(ret)
+                    }
+                    Ty::Unknown | Ty::Never => {
+                        for arg in args {
+                            self.expr(arg)?;
+                        }
+                        Ok(Ty::Unknown)
+                    }
+                    ty => Err(Error::new(
+                        format!("`{}` is not a function", self.show(&ty)),
+                        func.span,
+                    )),
+                }
+            }
+            ExprKind::If { cond, then, els } => {
+                self.expect(&Ty::Bool, cond)?;
+                let then_ty = self.block(then)?;
+                match els {
+                    Some(els) => {
+                        let els_ty = self.block(els)?;
+                        let origin = Some(value_span(then));
+                        self.join(&then_ty, &els_ty, value_span(els), origin)
+                    }
+                    // unit when the condition is false
+                    None => {
+                        self.unify(&Ty::Unit, &then_ty, value_span(then), None)?;
+                        Ok(Ty::Unit)
+                    }
+                }
+            }
+            ExprKind::Index { expr, index } => {
+                let ty = self.expr(expr)?;
+                self.expect(&Ty::Int, index)?;
+                if self.shallow(&ty) == Ty::String {
+                    return Ok(Ty::String);
+                }
+                match self.element(&ty, expr.span)? {
+                    Some(element) => Ok(element),
+                    None => Err(Error::new(
+                        format!("cannot index `{}`", self.show(&ty)),
+                        expr.span,
+                    )),
+                }
+            }
+            ExprKind::Slice { expr, start, end } => {
+                let ty = self.expr(expr)?;
+                for bound in [start, end].into_iter().flatten() {
+                    self.expect(&Ty::Int, bound)?;
+                }
+                if self.shallow(&ty) == Ty::String {
+                    return Ok(Ty::String);
+                }
+                match self.element(&ty, expr.span)? {
+                    Some(_) => Ok(ty),
+                    None => Err(Error::new(
+                        format!("cannot slice `{}`", self.show(&ty)),
+                        expr.span,
+                    )),
+                }
+            }
+            ExprKind::Tuple(elements) => Ok(Ty::Tuple(
+                elements
+                    .iter()
+                    .map(|e| self.expr(e))
+                    .collect::<Check<_>>()?,
+            )),
+            ExprKind::Array(elements) => {
+                let ty = self.unifier.fresh(Class::Any);
+                for element in elements {
+                    self.expect(&ty, element)?;
+                }
+                Ok(Ty::Array(Box::new(ty)))
+            }
+            ExprKind::TupleField { expr, index } => {
+                let ty = self.expr(expr)?;
+                match self.shallow(&ty) {
+                    Ty::Tuple(mut elements) if *index < elements.len() => {
+                        Ok(elements.swap_remove(*index))
+                    }
+                    // how many elements it has is not known
+                    Ty::Var(_) | Ty::Unknown | Ty::Never => Ok(Ty::Unknown),
+                    ty => Err(Error::new(
+                        format!("`{}` has no field `{}`", self.show(&ty), index),
+                        span,
+                    )),
+                }
+            }
+            ExprKind::Field { expr, field } => {
+                let ty = self.expr(expr)?;
+                self.field(&ty, field)
+            }
+            ExprKind::Struct { name, fields } => {
+                let Some((ty, types)) = self.instance(name.0) else {
+                    for (_, value) in fields {
+                        self.expr(value)?;
+                    }
+                    return Ok(Ty::Unknown);
+                };
+                for (field, value) in fields {
+                    // the compiler tells about fields the struct does not have
+                    match types.iter().find(|(name, _)| &**name == field.0) {
+                        Some((_, expected)) => self.expect(expected, value)?,
+                        None => self.expr(value)?,
+                    };
+                }
+                Ok(ty)
+            }
+            ExprKind::Path(enum_name, _) => Ok(self.enum_type(enum_name.0)),
+            ExprKind::Match { expr, arms } => {
+                let scrutinee = self.expr(expr)?;
+                let mut ty = Ty::Never;
+                for Arm { pattern, body } in arms {
+                    self.scopes.push(HashMap::new());
+                    self.bind(pattern, scrutinee.clone())?;
+                    let found = self.block(body)?;
+                    ty = self.join(&ty, &found, value_span(body), None)?;
+                    self.scopes.pop();
+                }
+                Ok(ty)
+            }
+        }
+    }
+
+    /// The type of arithmetic or ordering on `a` and `b`, which are of the
+    /// same type of `class`, but for ints mixed with floats, giving floats.
+    fn arith(
+        &mut self,
+        op: &Infix,
+        class: Class,
+        (a, lhs): (&Ty, &Expr),
+        (b, rhs): (&Ty, &Expr),
+        span: Span,
+    ) -> Check<Ty> {
+        let (shallow_a, shallow_b) = (self.shallow(a), self.shallow(b));
+        if shallow_a.is_number() && shallow_b.is_number() && shallow_a != shallow_b {
+            return Ok(Ty::Float);
+        }
+        for ty in [a, b] {
+            let bound = self.unifier.fresh(class);
+            if self.unifier.unify(&bound, ty, span).is_err() {
+                let msg = format!(
+                    "cannot apply `{}` to `{}` and `{}`",
+                    op,
+                    self.show(a),
+                    self.show(b)
+                );
+                return Err(Error::new(msg, span));
+            }
+        }
+        self.unify(a, b, rhs.span, Some(lhs.span))?;
+        Ok(match (shallow_a, shallow_b) {
+            // an unknown int or float
+            (Ty::Unknown, _) | (_, Ty::Unknown) => Ty::Unknown,
+            _ => a.clone(),
+        })
+    }
+
+    /// The type of a value which is either of `a`, coming from `origin`, or
+    /// of `b`, found at `span`. An int and a float join into a float, as the
+    /// VM mixes them, and a value of unknown type makes the join unknown.
+    fn join(&mut self, a: &Ty, b: &Ty, span: Span, origin: Option<Span>) -> Check<Ty> {
+        match (self.shallow(a), self.shallow(b)) {
+            (Ty::Never, _) => Ok(b.clone()),
+            (_, Ty::Never) => Ok(a.clone()),
+            (Ty::Unknown, _) | (_, Ty::Unknown) => Ok(Ty::Unknown),
+            (x, y) if x.is_number() && y.is_number() && x != y => Ok(Ty::Float),
+            _ => {
+                self.unify(a, b, span, origin)?;
+                Ok(a.clone())
+            }
+        }
+    }
+
+    /// What a variadic builtin returns, when `func` is one.
+    fn variadic(&self, func: &Expr) -> Option<Ty> {
+        let ExprKind::Id(ident) = &func.kind else {
+            return None;
+        };
+        let defined = self.scopes.iter().any(|scope| scope.contains_key(ident.0))
+            || self.globals.contains_key(ident.0);
+        (!defined).then(|| variadic(ident.0)).flatten()
+    }
+
+    /// The type of the elements of `ty` as an array, or `None` when it is
+    /// not one. A type not found yet is taken to be an array.
+    fn element(&mut self, ty: &Ty, span: Span) -> Check<Option<Ty>> {
+        match self.shallow(ty) {
+            Ty::Array(element) => Ok(Some(*element)),
+            Ty::Var(_) => {
+                let element = self.unifier.fresh(Class::Any);
+                let array = Ty::Array(Box::new(element.clone()));
+                self.unify(ty, &array, span, None)?;
+                Ok(Some(element))
+            }
+            Ty::Unknown | Ty::Never => Ok(Some(Ty::Unknown)),
+            _ => Ok(None),
+        }
+    }
+
+    /// The type of `field` of a value of type `ty`. A value whose type is
+    /// not found yet is taken to be of the only struct with the field, if any.
+    fn field(&mut self, ty: &Ty, field: &Ident) -> Check<Ty> {
+        match self.shallow(ty) {
+            Ty::Struct(name, args) => {
+                let def = &self.structs[&*name];
+                match def.fields.iter().find(|(name, _)| &**name == field.0) {
+                    Some((_, found)) => Ok(found.substitute(&def.params, &args)),
+                    None => Err(Error::new(
+                        format!("`{}` has no field `{}`", self.show(ty), field),
+                        field.1,
+                    )),
+                }
+            }
+            Ty::Var(_) => {
+                let mut owners = (self.structs.iter())
+                    .filter(|(_, def)| def.fields.iter().any(|(name, _)| &**name == field.0))
+                    .map(|(name, _)| name.clone());
+                let (Some(name), None) = (owners.next(), owners.next()) else {
+                    return Ok(Ty::Unknown);
+                };
+                let (found, types) = self.instance(&name).unwrap();
+                self.unify(ty, &found, field.1, None)?;
+                let (_, ty) = types
+                    .into_iter()
+                    .find(|(name, _)| &**name == field.0)
+                    .unwrap();
+                Ok(ty)
+            }
+            Ty::Unknown | Ty::Never => Ok(Ty::Unknown),
+            ty => Err(Error::new(
+                format!("`{}` has no field `{}`", self.show(&ty), field),
+                field.1,
+            )),
+        }
+    }
+
+    /// A type of the struct `name`, with fresh variables for its parameters,
+    /// and the types of its fields in it, or `None` if there is no such struct.
+    fn instance(&mut self, name: &str) -> Option<(Ty, Fields)> {
+        let def = self.structs.get(name)?;
+        let (params, fields) = (def.params.clone(), def.fields.clone());
+        let args: Vec<_> = (params.iter())
+            .map(|_| self.unifier.fresh(Class::Any))
+            .collect();
+        let fields = (fields.into_iter())
+            .map(|(name, ty)| (name, ty.substitute(&params, &args)))
+            .collect();
+        Some((Ty::Struct(name.into(), args), fields))
+    }
+
+    /// Declares the variables of `pattern`, matched against a value of type `ty`.
+    fn bind(&mut self, pattern: &'a Pattern<'a>, ty: Ty) -> Check<()> {
+        let span = pattern.span();
+        let mismatch = |checker: &Self| {
+            let msg = format!("this pattern cannot match a `{}`", checker.show(&ty));
+            Error::new(msg, span)
+        };
+        match pattern {
+            Pattern::Id(ident) => self.declare(ident.0, ty),
+            Pattern::Wildcard(_) => {}
+            Pattern::Tuple(patterns, _) => {
+                let types: Vec<_> = match self.shallow(&ty) {
+                    Ty::Unknown | Ty::Never => vec![Ty::Unknown; patterns.len()],
+                    _ => {
+                        let types: Vec<_> = (patterns.iter())
+                            .map(|_| self.unifier.fresh(Class::Any))
+                            .collect();
+                        let tuple = Ty::Tuple(types.clone());
+                        if self.unifier.unify(&ty, &tuple, span).is_err() {
+                            return Err(mismatch(self));
+                        }
+                        types
+                    }
+                };
+                for (pattern, ty) in patterns.iter().zip(types) {
+                    self.bind(pattern, ty)?;
+                }
+            }
+            Pattern::Literal(literal, _) => {
+                let found = literal_type(literal);
+                // numbers compare by value, whatever their type
+                if !(self.shallow(&ty).is_number() && found.is_number())
+                    && self.unifier.unify(&ty, &found, span).is_err()
+                {
+                    return Err(mismatch(self));
+                }
+            }
+            Pattern::Variant {
+                enum_name, fields, ..
+            } => {
+                let found = self.enum_type(enum_name.0);
+                if self.unifier.unify(&ty, &found, span).is_err() {
+                    return Err(mismatch(self));
+                }
+                for pattern in fields {
+                    self.bind(pattern, Ty::Unknown)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn declare(&mut self, name: &'a str, ty: Ty) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name, Scheme::mono(ty));
+        }
+    }
+
+    /// The type of a variable. What the host defines is not known.
+    fn lookup(&mut self, name: &str) -> Ty {
+        let local = self.scopes.iter().rev().find_map(|scope| scope.get(name));
+        match (local.or_else(|| self.globals.get(name))).or_else(|| self.builtins.get(name)) {
+            Some(scheme) => {
+                let scheme = scheme.clone();
+                self.unifier.instantiate(&scheme)
+            }
+            None => Ty::Unknown,
+        }
+    }
+
+    fn enum_type(&self, name: &str) -> Ty {
+        match self.enums.contains(name) {
+            true => Ty::Enum(name.into()),
+            false => Ty::Unknown,
+        }
+    }
+
+    /// Checks that the value of `expr` can be used where `expected` is.
+    fn expect(&mut self, expected: &Ty, expr: &'a Expr<'a>) -> Check<Ty> {
+        let found = self.expr(expr)?;
+        self.unify(expected, &found, expr.span, None)?;
+        Ok(found)
+    }
+
+    /// Makes `expected` the same type as `found`, the type of the value at
+    /// `span`. `origin` is where `expected` comes from, if known.
+    fn unify(&mut self, expected: &Ty, found: &Ty, span: Span, origin: Option<Span>) -> Check<()> {
+        let shown = (self.show(expected), self.show(found));
+        self.unifier
+            .unify(expected, found, span)
+            .map_err(|fail| match fail {
+                Fail::Class(class, ty) => {
+                    let msg = format!("expected {}, found `{}`", class.describe(), ty);
+                    Error::new(msg, span)
+                }
+                Fail::Mismatch(inferred) => Error {
+                    note: inferred.or(origin).filter(|origin| *origin != span),
+                    ..Error::new(format!("expected `{}`, found `{}`", shown.0, shown.1), span)
+                },
+            })
+    }
+
+    fn shallow(&self, ty: &Ty) -> Ty {
+        self.unifier.shallow(ty).0
+    }
+
+    /// How a type is shown in errors, with what is found of it so far.
+    fn show(&self, ty: &Ty) -> String {
+        self.unifier.resolve(ty).to_string()
+    }
+}
+
+fn literal_type(literal: &Literal) -> Ty {
+    match literal {
+        Literal::Int(_) => Ty::Int,
+        Literal::Float(_) => Ty::Float,
+        Literal::Boolean(_) => Ty::Bool,
+        Literal::String(_) => Ty::String,
+    }
+}
+
+/// The type parameters a function is declared with.
+fn type_params(function: &Function) -> Vec<(Rc<str>, Class)> {
+    (function.generics.iter())
+        .map(|g| (g.0.into(), Class::Any))
+        .collect()
+}
+
+/// Where the value of a block is, or the block when it has none.
+fn value_span(block: &Block) -> Span {
+    block.value.as_ref().map_or(block.span, |value| value.span)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(input: &str) -> Result<Vec<(String, String)>, TypeError> {
+        check_program(&parse_file(input).unwrap(), input)
+    }
+
+    /// The inferred type of each global of `input`, as `name: type`.
+    fn types(input: &str) -> Vec<String> {
+        let types = check(input).unwrap_or_else(|e| panic!("{}", e));
+        types
+            .iter()
+            .map(|(name, ty)| format!("{}: {}", name, ty))
+            .collect()
+    }
+
+    /// The message and the position of the error in `input`.
+    fn type_error(input: &str) -> (String, Position) {
+        let e = check(input).unwrap_err();
+        (e.msg, e.start)
+    }
+
+    #[test]
+    fn test_annotations() {
+        let input = r#"
+            struct P { x, y }
+            enum Shape { Circle(r), Empty }
+            let sq = fn(n: int) -> int { n * n };
+            let half = fn(x: float) -> float { x / 2.0 };
+            let origin: P = P { x: 0, y: 0 };
+            let main = fn() -> (int, [string]) {
+                let a: int = sq(3);
+                let (b, c): (float, bool) = (half(1.0), a > 2);
+                let names: [string] = [];
+                push(names, "a");
+                let s: Shape = Shape::Circle(b);
+                let r = match s { Shape::Circle(r) => r, _ => 0 };
+                (a + r, names)
+            };
+        "#;
+        assert!(check(input).is_ok());
+    }
+
+    #[test]
+    fn test_inference() {
+        let input = r#"
+            let add = fn(a, b) { a + b };
+            let neg = fn(n) { -n };
+            let first = fn(xs) { xs[0] };
+            let apply = fn(f, x) { f(x) };
+            let count = 3;
+            let main = fn() { (id(1), id("a"), add(1, 2), add("a", "b"), neg(1.5)) };
+            let id = fn(x) { x };
+        "#;
+        assert_eq!(
+            types(input),
+            [
+                "add: fn<T: num | string>(T, T) -> T",
+                "neg: fn<T: num>(T) -> T",
+                "first: fn<T>([T]) -> T",
+                "apply: fn<T, U>(fn(T) -> U, T) -> U",
+                "count: int",
+                "main: fn() -> (int, string, int, string, float)",
+                "id: fn<T>(T) -> T",
+            ]
+        );
+
+        // mutually recursive functions are inferred together
+        let input = r#"
+            let even = fn(n) { if n == 0 { true } else { odd(n - 1) } };
+            let odd = fn(n) { if n == 0 { false } else { even(n - 1) } };
+        "#;
+        assert_eq!(
+            types(input),
+            ["even: fn(int) -> bool", "odd: fn(int) -> bool"]
+        );
+
+        // the fields of variants are not known, so neither is arithmetic on them
+        let input = r#"
+            enum Shape { Circle(r), Rect(w, h) }
+            let area = fn(shape) {
+                match shape {
+                    Shape::Circle(r) => 3.14 * r * r,
+                    Shape::Rect(w, h) => w * h,
+                }
+            };
+            let pick = fn(c) { if c { 1 } else { 2.5 } };
+        "#;
+        assert_eq!(
+            types(input),
+            ["area: fn(Shape) -> any", "pick: fn(bool) -> float"]
+        );
+
+        // numbers given ints and floats are floats, as the VM promotes the ints
+        let input = r#"
+            let add = fn(a, b) { a + b };
+            let main = (min(1, 2.5), max(2, 3), add(1, 0.5));
+        "#;
+        assert_eq!(
+            types(input),
+            [
+                "add: fn<T: num | string>(T, T) -> T",
+                "main: (float, int, float)",
+            ]
+        );
+        let (msg, _) = type_error("let main = max(\"a\", 1.5);");
+        assert_eq!(msg, "expected `float`, found `string`");
+    }
+
+    #[test]
+    fn test_inference_errors() {
+        let (msg, _) = type_error("let add = fn(a, b) { a + b }; let main = add(true, 1);");
+        assert_eq!(msg, "expected a number or a string, found `bool`");
+
+        let (msg, _) = type_error("let add = fn(a, b) { a + b }; let main = add(1, true);");
+        assert_eq!(msg, "expected `int`, found `bool`");
+
+        let (msg, _) = type_error("let f = fn(x) { x(x) };");
+        assert_eq!(msg, "expected `_`, found `fn(_) -> _`");
+
+        let (msg, _) = type_error("let f = fn(x) { -x }; let main = f(\"a\");");
+        assert_eq!(msg, "expected a number, found `string`");
+
+        // a local is not generic
+        let (msg, _) = type_error("let f = fn() { let id = fn(x) { x }; (id(1), id(true)) };");
+        assert_eq!(msg, "expected `int`, found `bool`");
+
+        // an assignment keeps the type of the variable, annotated or not
+        let (msg, _) = type_error("let main = fn() { let x: int = 1; x = 1.5; x }();");
+        assert_eq!(msg, "expected `int`, found `float`");
+        let (msg, _) = type_error("let f = fn() { let sum = 0; sum = sum + 0.5; sum };");
+        assert_eq!(msg, "expected `int`, found `float`");
+        let input = r#"
+            let half = fn(n: int) -> int { n / 2 };
+            let main = fn() {
+                let x = 3;
+                let out = 0;
+                for i in 0..2 { out = half(x); x = 2.5; };
+                out
+            }();
+        "#;
+        let (msg, _) = type_error(input);
+        assert_eq!(msg, "expected `int`, found `float`");
+    }
+
+    #[test]
+    fn test_conflict_note() {
+        let input = "let f = fn(xs) {\n    push(xs, 1);\n    push(xs, \"a\");\n};";
+        let e = check(input).unwrap_err();
+        assert_eq!(e.msg, "expected `int`, found `string`");
+        assert_eq!(e.start, Position { line: 3, col: 14 });
+        let note = e.note.unwrap();
+        assert_eq!(note.msg, "the other type comes from here");
+        assert_eq!(note.start, Position { line: 2, col: 14 });
+    }
+
+    #[test]
+    fn test_generics() {
+        let input = r#"
+            struct Pair<A, B> { first: A, second: B }
+            struct Named { name: string, tags }
+            let map = fn<T, U>(f: fn(T) -> U, xs: Array<T>) -> [U] {
+                let out = [];
+                for x in xs { push(out, f(x)); };
+                out
+            };
+            let swap = fn<A, B>(p: Pair<A, B>) -> Pair<B, A> {
+                Pair { first: p.second, second: p.first }
+            };
+            let apply: fn(fn(int) -> int, int) -> int = fn(f, x) { f(x) };
+            let name = fn(n) { n.name };
+            let main = fn() {
+                let id = fn<T>(x: T) -> T { x };
+                let p = Pair { first: id(1), second: id("a") };
+                (map(fn(n) { n > 1 }, [1, 2]), swap(p).first, p.second)
+            };
+        "#;
+        assert_eq!(
+            types(input),
+            [
+                "map: fn<T, U>(fn(T) -> U, [T]) -> [U]",
+                "swap: fn<A, B>(Pair<A, B>) -> Pair<B, A>",
+                "apply: fn(fn(int) -> int, int) -> int",
+                "name: fn(Named) -> string",
+                "main: fn() -> ([bool], string, string)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_generic_errors() {
+        let map = "let map = fn<T, U>(f: fn(T) -> U, xs: [T]) -> [U] { [] };";
+        let (msg, _) = type_error(&format!(
+            "{map} let main = map(fn(s: string) {{ s }}, [1]);"
+        ));
+        assert_eq!(msg, "expected `[string]`, found `[int]`");
+
+        let (msg, _) = type_error(&format!("{map} let main = map(1, [1]);"));
+        assert_eq!(msg, "expected `fn(_) -> _`, found `int`");
+
+        // a type parameter stands for any type, so only matches itself
+        let (msg, _) = type_error("let f = fn<T>(x: T) -> int { x };");
+        assert_eq!(msg, "expected `int`, found `T`");
+        let (msg, _) = type_error("let f = fn<T>(x: T) -> T { x + x };");
+        assert_eq!(msg, "cannot apply `+` to `T` and `T`");
+
+        let (msg, _) = type_error("struct P<T> { x: T } let f = fn(p: P) { p };");
+        assert_eq!(msg, "`P` takes 1 type arguments, found 0");
+        let (msg, _) = type_error("let f = fn(x: Array<int, int>) { x };");
+        assert_eq!(msg, "`Array` takes 1 type arguments, found 2");
+        let (msg, _) = type_error("struct P { x: int } let f = fn(p: P) { p.x = true; };");
+        assert_eq!(msg, "expected `int`, found `bool`");
+        let (msg, _) = type_error("struct P { x: T } let main = 1;");
+        assert_eq!(msg, "unknown type `T`");
+    }
+
+    #[test]
+    fn test_mismatches() {
+        let (msg, at) = type_error("let a: int = \"one\";");
+        assert_eq!(msg, "expected `int`, found `string`");
+        assert_eq!(at, Position { line: 1, col: 14 });
+
+        let (msg, at) = type_error("let f = fn(n: int) -> int { n };\nlet main = f(true);");
+        assert_eq!(msg, "expected `int`, found `bool`");
+        assert_eq!(at, Position { line: 2, col: 14 });
+
+        let (msg, _) = type_error("let f = fn(n: int) -> bool { return n; };");
+        assert_eq!(msg, "expected `bool`, found `int`");
+
+        let (msg, _) = type_error("let f = fn(n: int) { n }; let main = f(1, 2);");
+        assert_eq!(msg, "expected 1 arguments, found 2");
+
+        let (msg, _) = type_error("let f = fn() { let a = 1; a = \"a\"; };");
+        assert_eq!(msg, "expected `int`, found `string`");
+
+        let (msg, _) = type_error("let main = 1 + true;");
+        assert_eq!(msg, "cannot apply `+` to `int` and `bool`");
+
+        let (msg, _) = type_error("let main = if 1 { 2 };");
+        assert_eq!(msg, "expected `bool`, found `int`");
+
+        let (msg, _) = type_error("let t: (int, int) = (1, 2); let main = t.2;");
+        assert_eq!(msg, "`(int, int)` has no field `2`");
+
+        let (msg, _) = type_error("let f = fn(x: [int]) { x[0] = 1.5; };");
+        assert_eq!(msg, "expected `int`, found `float`");
+
+        let (msg, _) = type_error("let f = fn(n: int) { for i in n { } };");
+        assert_eq!(msg, "cannot iterate over `int`");
+
+        let (msg, _) = type_error("struct P { x } let f = fn(p: P) { p.y };");
+        assert_eq!(msg, "`P` has no field `y`");
+
+        let (msg, _) = type_error("let f = fn(p: Q) { };");
+        assert_eq!(msg, "unknown type `Q`");
+
+        let (msg, _) = type_error("let f = fn(s: string) { match s { 1 => 0, _ => 1 } };");
+        assert_eq!(msg, "this pattern cannot match a `string`");
+    }
+
+    #[test]
+    fn test_returns() {
+        // both branches return, so the end of the body is never reached
+        let input = "let f = fn(n: int) -> int { if n < 2 { return n; } else { return 0; } };";
+        assert!(check(input).is_ok());
+
+        // the `if` is returned, and is unit when it does not return
+        let (msg, at) = type_error("let f = fn(n: int) -> int { if n < 2 { return n; } };");
+        assert_eq!(msg, "expected `int`, found `()`");
+        assert_eq!(at, Position { line: 1, col: 29 });
+
+        let (msg, at) = type_error("let f = fn(n: int) -> int { let m = n; };");
+        assert_eq!(msg, "expected `int`, found `()` at the end of the function");
+        assert_eq!(at, Position { line: 1, col: 9 });
+
+        // the type of an unannotated function follows from its returns
+        let (msg, _) = type_error("let f = fn() { 1 }; let main: bool = f();");
+        assert_eq!(msg, "expected `bool`, found `int`");
+    }
+
+    #[test]
+    fn test_render_type_error() {
+        let e = check("let a = 1;\nlet b: bool = a;").unwrap_err();
+        assert_eq!(
+            e.render(Some("main.siren")),
+            "error: expected `bool`, found `int`\n \
+              --> main.siren:2:15\n  \
+               |\n\
+             2 | let b: bool = a;\n  \
+               |               ^\n\
+             note: the other type comes from here\n \
+              --> main.siren:1:9\n  \
+               |\n\
+             1 | let a = 1;\n  \
+               |         ^"
+        );
+    }
+}
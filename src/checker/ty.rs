@@ -0,0 +1,177 @@
+use std::{fmt::Display, rc::Rc};
+
+/// The static type of a value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Ty {
+    Int,
+    Float,
+    Bool,
+    String,
+    Range,
+    Unit,
+    Tuple(Vec<Ty>),
+    Array(Box<Ty>),
+    Fn(Vec<Ty>, Box<Ty>),
+    /// A struct, with the arguments of its type parameters.
+    Struct(Rc<str>, Vec<Ty>),
+    Enum(Rc<str>),
+    /// A type being inferred, found from how the value is used.
+    Var(usize),
+    /// A parameter of a generic type, like `T` in `fn<T>(T) -> T`.
+    Param(Rc<str>),
+    /// Not known before running, like a field of a struct: anything goes.
+    Unknown,
+    /// The type of what never has a value, like an `if` returning from both branches.
+    Never,
+}
+
+impl Ty {
+    pub fn is_number(&self) -> bool {
+        matches!(self, Ty::Int | Ty::Float)
+    }
+
+    /// `self` with the parameters of `params` replaced by the types of `args`.
+    pub fn substitute(&self, params: &[Rc<str>], args: &[Ty]) -> Ty {
+        let substitute = |types: &[Ty]| types.iter().map(|t| t.substitute(params, args)).collect();
+        match self {
+            Ty::Param(name) => match params.iter().position(|param| param == name) {
+                Some(i) => args[i].clone(),
+                None => self.clone(),
+            },
+            Ty::Tuple(types) => Ty::Tuple(substitute(types)),
+            Ty::Array(ty) => Ty::Array(Box::new(ty.substitute(params, args))),
+            Ty::Fn(types, ret) => Ty::Fn(substitute(types), Box::new(ret.substitute(params, args))),
+            Ty::Struct(name, types) => Ty::Struct(name.clone(), substitute(types)),
+            ty => ty.clone(),
+        }
+    }
+
+    /// The variables in `self`, in order of appearance.
+    pub fn vars(&self, vars: &mut Vec<usize>) {
+        match self {
+            Ty::Var(v) if !vars.contains(v) => vars.push(*v),
+            Ty::Tuple(types) | Ty::Struct(_, types) => types.iter().for_each(|t| t.vars(vars)),
+            Ty::Array(ty) => ty.vars(vars),
+            Ty::Fn(types, ret) => {
+                types.iter().for_each(|t| t.vars(vars));
+                ret.vars(vars);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The types a variable or a parameter can stand for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Class {
+    Any,
+    /// `int` or `float`, for arithmetic.
+    Num,
+    /// `int`, `float` or `string`, for `+` and ordering.
+    Ordered,
+}
+
+impl Class {
+    /// The types of both classes.
+    pub fn meet(self, other: Class) -> Class {
+        match (self, other) {
+            (Class::Any, class) | (class, Class::Any) => class,
+            (Class::Num, _) | (_, Class::Num) => Class::Num,
+            _ => Class::Ordered,
+        }
+    }
+
+    /// Whether the class has `ty`, which is not a variable.
+    pub fn admits(self, ty: &Ty) -> bool {
+        match self {
+            Class::Any => true,
+            Class::Num => matches!(ty, Ty::Int | Ty::Float | Ty::Unknown | Ty::Never),
+            Class::Ordered => {
+                matches!(
+                    ty,
+                    Ty::Int | Ty::Float | Ty::String | Ty::Unknown | Ty::Never
+                )
+            }
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            Class::Any => "a value",
+            Class::Num => "a number",
+            Class::Ordered => "a number or a string",
+        }
+    }
+}
+
+impl Display for Class {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Class::Any => Ok(()),
+            Class::Num => write!(f, "num"),
+            Class::Ordered => write!(f, "num | string"),
+        }
+    }
+}
+
+/// The type of a global, generic over its parameters.
+#[derive(Debug, Clone)]
+pub struct Scheme {
+    pub params: Vec<(Rc<str>, Class)>,
+    pub ty: Ty,
+}
+
+impl Scheme {
+    pub fn mono(ty: Ty) -> Self {
+        Scheme {
+            params: Vec::new(),
+            ty,
+        }
+    }
+}
+
+impl Display for Scheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let params: Vec<_> = (self.params.iter())
+            .map(|(name, class)| match class {
+                Class::Any => name.to_string(),
+                class => format!("{}: {}", name, class),
+            })
+            .collect();
+        match &self.ty {
+            _ if params.is_empty() => write!(f, "{}", self.ty),
+            Ty::Fn(types, ret) => {
+                write!(f, "fn<{}>({}) -> {}", params.join(", "), list(types), ret)
+            }
+            ty => write!(f, "<{}> {}", params.join(", "), ty),
+        }
+    }
+}
+
+fn list(types: &[Ty]) -> String {
+    let types: Vec<_> = types.iter().map(|t| t.to_string()).collect();
+    types.join(", ")
+}
+
+impl Display for Ty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Ty::Int => write!(f, "int"),
+            Ty::Float => write!(f, "float"),
+            Ty::Bool => write!(f, "bool"),
+            Ty::String => write!(f, "string"),
+            Ty::Range => write!(f, "range"),
+            Ty::Unit => write!(f, "()"),
+            Ty::Tuple(types) if types.len() == 1 => write!(f, "({},)", types[0]),
+            Ty::Tuple(types) => write!(f, "({})", list(types)),
+            Ty::Array(ty) => write!(f, "[{}]", ty),
+            Ty::Fn(types, ret) => write!(f, "fn({}) -> {}", list(types), ret),
+            Ty::Struct(name, types) if !types.is_empty() => write!(f, "{}<{}>", name, list(types)),
+            Ty::Struct(name, _) | Ty::Enum(name) | Ty::Param(name) => write!(f, "{}", name),
+            // not found yet
+            Ty::Var(_) => write!(f, "_"),
+            Ty::Unknown => write!(f, "any"),
+            Ty::Never => write!(f, "!"),
+        }
+    }
+}
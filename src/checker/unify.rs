@@ -0,0 +1,201 @@
+use std::{collections::HashSet, rc::Rc};
+
+use super::ty::{Class, Scheme, Ty};
+use crate::parser::Span;
+
+/// What is known of a type variable.
+#[derive(Debug, Clone)]
+enum Var {
+    /// Not found yet: it may be any type of the class.
+    Free(Class),
+    /// Found to be the type, by what is at the span.
+    Bound(Ty, Span),
+}
+
+/// Why two types cannot be the same.
+#[derive(Debug)]
+pub enum Fail {
+    /// They differ. The span is where a variable they went through was found, if any.
+    Mismatch(Option<Span>),
+    /// A variable of the class would be the type.
+    Class(Class, Ty),
+}
+
+/// The type variables of an inference and what they are found to be.
+#[derive(Debug, Default, Clone)]
+pub struct Unifier {
+    vars: Vec<Var>,
+}
+
+impl Unifier {
+    pub fn fresh(&mut self, class: Class) -> Ty {
+        self.vars.push(Var::Free(class));
+        Ty::Var(self.vars.len() - 1)
+    }
+
+    /// `ty` past the variables found at its top, with where the last of them was found.
+    pub fn shallow(&self, ty: &Ty) -> (Ty, Option<Span>) {
+        let mut ty = ty.clone();
+        let mut origin = None;
+        while let Ty::Var(v) = ty {
+            match &self.vars[v] {
+                Var::Bound(found, span) => {
+                    origin = Some(*span);
+                    ty = found.clone();
+                }
+                Var::Free(_) => break,
+            }
+        }
+        (ty, origin)
+    }
+
+    /// The class of `ty` when it is a variable not found yet.
+    pub fn class(&self, ty: &Ty) -> Option<Class> {
+        match self.shallow(ty).0 {
+            Ty::Var(v) => match self.vars[v] {
+                Var::Free(class) => Some(class),
+                Var::Bound(..) => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// `ty` with all the variables found so far replaced.
+    pub fn resolve(&self, ty: &Ty) -> Ty {
+        let resolve = |types: &[Ty]| types.iter().map(|t| self.resolve(t)).collect();
+        match self.shallow(ty).0 {
+            Ty::Tuple(types) => Ty::Tuple(resolve(&types)),
+            Ty::Array(ty) => Ty::Array(Box::new(self.resolve(&ty))),
+            Ty::Fn(types, ret) => Ty::Fn(resolve(&types), Box::new(self.resolve(&ret))),
+            Ty::Struct(name, types) => Ty::Struct(name, resolve(&types)),
+            ty => ty,
+        }
+    }
+
+    /// Makes `a` and `b` the same type, where `span` is what requires it.
+    pub fn unify(&mut self, a: &Ty, b: &Ty, span: Span) -> Result<(), Fail> {
+        let (a, a_origin) = self.shallow(a);
+        let (b, b_origin) = self.shallow(b);
+        let origin = a_origin.or(b_origin);
+        let result = match (a, b) {
+            (Ty::Never, _) | (_, Ty::Never) => Ok(()),
+            (Ty::Var(x), Ty::Var(y)) if x == y => Ok(()),
+            // a type found elsewhere keeps where it was found
+            (Ty::Var(x), ty) => self.bind(x, ty, b_origin.unwrap_or(span)),
+            (ty, Ty::Var(x)) => self.bind(x, ty, a_origin.unwrap_or(span)),
+            (Ty::Unknown, _) | (_, Ty::Unknown) => Ok(()),
+            (Ty::Tuple(a), Ty::Tuple(b)) if a.len() == b.len() => self.unify_all(&a, &b, span),
+            (Ty::Array(a), Ty::Array(b)) => self.unify(&a, &b, span),
+            (Ty::Struct(x, a), Ty::Struct(y, b)) if x == y => self.unify_all(&a, &b, span),
+            (Ty::Fn(a, r), Ty::Fn(b, s)) if a.len() == b.len() => self
+                .unify_all(&a, &b, span)
+                .and_then(|_| self.unify(&r, &s, span)),
+            (a, b) if a == b => Ok(()),
+            _ => Err(Fail::Mismatch(None)),
+        };
+        result.map_err(|fail| match fail {
+            Fail::Mismatch(None) => Fail::Mismatch(origin),
+            fail => fail,
+        })
+    }
+
+    fn unify_all(&mut self, a: &[Ty], b: &[Ty], span: Span) -> Result<(), Fail> {
+        a.iter()
+            .zip(b)
+            .try_for_each(|(a, b)| self.unify(a, b, span))
+    }
+
+    fn bind(&mut self, var: usize, ty: Ty, span: Span) -> Result<(), Fail> {
+        let Var::Free(class) = self.vars[var] else {
+            unreachable!("bind a variable already found")
+        };
+        match &ty {
+            Ty::Var(other) => {
+                let Var::Free(other_class) = self.vars[*other] else {
+                    unreachable!("bind to a variable already found")
+                };
+                self.vars[*other] = Var::Free(class.meet(other_class));
+            }
+            // a type cannot contain itself
+            ty if self.occurs(var, ty) => return Err(Fail::Mismatch(None)),
+            ty if !class.admits(ty) => return Err(Fail::Class(class, ty.clone())),
+            _ => {}
+        }
+        self.vars[var] = Var::Bound(ty, span);
+        Ok(())
+    }
+
+    fn occurs(&self, var: usize, ty: &Ty) -> bool {
+        let mut vars = Vec::new();
+        self.resolve(ty).vars(&mut vars);
+        vars.contains(&var)
+    }
+
+    /// The variables left in `ty`.
+    pub fn free_vars(&self, ty: &Ty) -> Vec<usize> {
+        let mut vars = Vec::new();
+        self.resolve(ty).vars(&mut vars);
+        vars
+    }
+
+    /// `ty` made generic over `params`, the parameters it is declared with,
+    /// and over the variables it has which are not in `env`.
+    pub fn generalize(
+        &self,
+        ty: &Ty,
+        env: &HashSet<usize>,
+        params: Vec<(Rc<str>, Class)>,
+    ) -> Scheme {
+        let ty = self.resolve(ty);
+        let declared: Vec<_> = params.iter().map(|(name, _)| name.clone()).collect();
+        let mut names = (0..)
+            .map(|n| -> Rc<str> {
+                match n {
+                    0..=3 => ["T", "U", "V", "W"][n].into(),
+                    n => format!("T{}", n).into(),
+                }
+            })
+            .filter(|name| !declared.contains(name));
+        let mut params = params;
+        let mut args = Vec::new();
+        for var in self.free_vars(&ty) {
+            if env.contains(&var) {
+                continue;
+            }
+            let name = names.next().unwrap();
+            let Var::Free(class) = self.vars[var] else {
+                unreachable!()
+            };
+            params.push((name.clone(), class));
+            args.push((var, Ty::Param(name)));
+        }
+        Scheme {
+            params,
+            ty: replace_vars(&ty, &args),
+        }
+    }
+
+    /// A type of the scheme, with fresh variables for its parameters.
+    pub fn instantiate(&mut self, scheme: &Scheme) -> Ty {
+        let names: Vec<_> = scheme.params.iter().map(|(name, _)| name.clone()).collect();
+        let vars: Vec<_> = (scheme.params.iter())
+            .map(|(_, class)| self.fresh(*class))
+            .collect();
+        scheme.ty.substitute(&names, &vars)
+    }
+}
+
+fn replace_vars(ty: &Ty, args: &[(usize, Ty)]) -> Ty {
+    let replace = |types: &[Ty]| types.iter().map(|t| replace_vars(t, args)).collect();
+    match ty {
+        Ty::Var(v) => match args.iter().find(|(var, _)| var == v) {
+            Some((_, param)) => param.clone(),
+            None => ty.clone(),
+        },
+        Ty::Tuple(types) => Ty::Tuple(replace(types)),
+        Ty::Array(ty) => Ty::Array(Box::new(replace_vars(ty, args))),
+        Ty::Fn(types, ret) => Ty::Fn(replace(types), Box::new(replace_vars(ret, args))),
+        Ty::Struct(name, types) => Ty::Struct(name.clone(), replace(types)),
+        ty => ty.clone(),
+    }
+}
@@ -1,19 +1,104 @@
 use super::*;
-use std::ops::Index;
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    ops::Index,
+    rc::Rc,
+};
+
+/// The structs and enums declared so far. A REPL keeps them across lines.
+pub type Types = Rc<RefCell<TypeTable>>;
+
+/// Declared types by name. Structs and enums share one namespace.
+#[derive(Debug, Default, Clone)]
+pub struct TypeTable {
+    structs: HashMap<Rc<str>, Rc<StructType>>,
+    enums: HashMap<Rc<str>, Rc<EnumType>>,
+}
+
+impl TypeTable {
+    fn declare(&self, name: &str) -> Result<(), String> {
+        match self.structs.contains_key(name) || self.enums.contains_key(name) {
+            true => Err(format!("Type `{}` is declared twice", name)),
+            false => Ok(()),
+        }
+    }
+}
+
+/// How many constants a chunk can hold, as instructions refer to them by a `u16`.
+pub const MAX_CONSTANTS: usize = u16::MAX as usize + 1;
 
 // chunk of bytecode, and constants
+#[derive(Debug)]
 pub struct Chunk {
     code: Vec<Inst>,
     constants: Vec<Value>,
+    // where the scalar constants are, so that adding one again is cheap
+    scalars: HashMap<Scalar, u16>,
+    // the span of source each instruction was compiled from
+    spans: Vec<Span>,
+    // the span given to the next instructions
+    span: Span,
+    source: Rc<str>,
+    // string constants, shared with the chunks of nested functions
+    strings: Rc<RefCell<HashSet<Rc<str>>>>,
+    // struct layouts and enum variants, shared the same way
+    types: Types,
+}
+
+impl Default for Chunk {
+    fn default() -> Self {
+        Self::with_source(Rc::from(""))
+    }
 }
 
 impl Chunk {
-    pub fn new() -> Self {
+    pub fn with_source(source: Rc<str>) -> Self {
         Chunk {
             code: Vec::new(),
             constants: Vec::new(),
+            scalars: HashMap::new(),
+            spans: Vec::new(),
+            span: Span::default(),
+            source,
+            strings: Rc::default(),
+            types: Rc::default(),
+        }
+    }
+
+    /// A chunk that sees, and adds to, the given types.
+    pub fn with_types(source: Rc<str>, types: &Types) -> Self {
+        Chunk {
+            types: types.clone(),
+            ..Self::with_source(source)
         }
     }
+
+    /// An empty chunk for a function defined in this one, compiled from the same source.
+    pub fn child(&self) -> Self {
+        Chunk {
+            span: self.span,
+            strings: self.strings.clone(),
+            types: self.types.clone(),
+            ..Self::with_source(self.source.clone())
+        }
+    }
+
+    /// Sets the span of the next instructions, returning the previous one.
+    pub fn set_span(&mut self, span: Span) -> Span {
+        std::mem::replace(&mut self.span, span)
+    }
+
+    /// Where the instruction at `pc` comes from in the source.
+    pub fn position(&self, pc: usize) -> Position {
+        let offset = self.spans.get(pc).map_or(0, |span| span.start);
+        Position::of(&self.source, offset)
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
     pub fn len(&self) -> usize {
         self.code.len()
     }
@@ -22,17 +107,103 @@ impl Chunk {
         &self.constants[index]
     }
 
-    pub fn add_constant(&mut self, value: Value) -> u8 {
+    /// Adds a constant, or finds it when it is already there. Fails once the
+    /// chunk holds `MAX_CONSTANTS`.
+    pub fn add_constant(&mut self, value: Value) -> Result<u16, String> {
         if let Some(ind) = get_const_ind(self, &value) {
-            ind
-        } else {
-            self.constants.push(value);
-            (self.constants.len() - 1) as u8
+            return Ok(ind);
+        }
+        let ind = u16::try_from(self.constants.len()).map_err(|_| {
+            format!(
+                "Too many constants in one function, at most {} are allowed",
+                MAX_CONSTANTS
+            )
+        })?;
+        if let Some(scalar) = Scalar::of(&value) {
+            self.scalars.insert(scalar, ind);
+        }
+        self.constants.push(value);
+        Ok(ind)
+    }
+
+    /// Adds a string constant. Equal strings share one allocation across the
+    /// chunk and the chunks of its nested functions.
+    pub fn add_string(&mut self, s: &str) -> Result<u16, String> {
+        let interned = self.intern(s);
+        self.add_constant(Value::String(interned))
+    }
+
+    fn intern(&self, s: &str) -> Rc<str> {
+        let mut strings = self.strings.borrow_mut();
+        match strings.get(s) {
+            Some(interned) => interned.clone(),
+            None => {
+                let interned: Rc<str> = Rc::from(s);
+                strings.insert(interned.clone());
+                interned
+            }
         }
     }
 
+    /// Declares a struct, failing when a type of that name already exists.
+    pub fn add_struct(&mut self, name: &str, fields: &[&str]) -> Result<(), String> {
+        let ty = StructType {
+            name: name.to_string(),
+            fields: fields.iter().map(|field| self.intern(field)).collect(),
+        };
+        let mut types = self.types.borrow_mut();
+        types.declare(name)?;
+        types.structs.insert(Rc::from(name), Rc::new(ty));
+        Ok(())
+    }
+
+    pub fn get_struct(&self, name: &str) -> Option<Rc<StructType>> {
+        self.types.borrow().structs.get(name).cloned()
+    }
+
+    /// Declares an enum, failing when a type of that name already exists.
+    pub fn add_enum(&mut self, ty: EnumType) -> Result<(), String> {
+        let mut types = self.types.borrow_mut();
+        types.declare(&ty.name)?;
+        types.enums.insert(Rc::from(&*ty.name), Rc::new(ty));
+        Ok(())
+    }
+
+    pub fn get_enum(&self, name: &str) -> Option<Rc<EnumType>> {
+        self.types.borrow().enums.get(name).cloned()
+    }
+
+    /// Where the field is in the structs that have one of that name: the
+    /// lowest such offset, or `None` when no struct has it.
+    pub fn field_offset(&self, field: &str) -> Option<usize> {
+        let types = self.types.borrow();
+        types
+            .structs
+            .values()
+            .filter_map(|ty| ty.fields.iter().position(|name| &**name == field))
+            .min()
+    }
+
     pub fn add_inst(&mut self, inst: Inst) {
         self.code.push(inst);
+        self.spans.push(self.span);
+    }
+
+    /// Adds a jump whose target is patched later, returning where it is.
+    pub fn add_jump(&mut self, inst: Inst) -> usize {
+        self.add_inst(inst);
+        self.code.len() - 1
+    }
+
+    /// Points the jump at `ind` to the next instruction to be added.
+    pub fn patch_jump(&mut self, ind: usize) {
+        let target = self.code.len();
+        match &mut self.code[ind] {
+            Inst::Jump(p) | Inst::JumpIfFalse(p) | Inst::JumpIfTrue(p) | Inst::ForNext(p) => {
+                *p = target
+            }
+            inst => unreachable!("patching a non-jump instruction: {inst:?}"),
+        }
     }
 }
 
@@ -46,16 +217,54 @@ impl Index<usize> for Chunk {
 
 pub fn disassemble_chunk(chunk: &Chunk, name: &str) {
     println!("== {} ==", name);
+    let mut last_line = 0;
     for (i, inst) in chunk.code.iter().enumerate() {
-        println!("{:04} {}", i, inst.disassemble(chunk));
+        let line = chunk.position(i).line;
+        let line_column = match line == last_line {
+            true => "   |".to_string(),
+            false => format!("{:4}", line),
+        };
+        last_line = line;
+        println!("{:04} {} {}", i, line_column, inst.disassemble(chunk));
+    }
+    // functions carry their own chunks
+    for constant in chunk.constants.iter() {
+        if let Value::Function(function) = constant {
+            disassemble_chunk(&function.chunk, &function.to_string());
+        }
     }
 }
 
-pub fn get_const_ind(chunk: &Chunk, value: &Value) -> Option<u8> {
-    chunk
-        .constants
-        .iter()
-        .enumerate()
-        .find(|(_, stored_value)| stored_value == &value)
-        .map(|(ind, _)| ind as u8)
+pub fn get_const_ind(chunk: &Chunk, value: &Value) -> Option<u16> {
+    match Scalar::of(value) {
+        Some(scalar) => chunk.scalars.get(&scalar).copied(),
+        // functions and types, which are few
+        None => (chunk.constants.iter())
+            .position(|stored_value| stored_value == value)
+            .map(|ind| ind as u16),
+    }
+}
+
+/// A constant which can be hashed. Floats are told apart by their bits, so
+/// that `0.0` and `-0.0` stay two constants.
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum Scalar {
+    Unit,
+    Bool(bool),
+    Int(i64),
+    Float(u64),
+    String(Rc<str>),
+}
+
+impl Scalar {
+    fn of(value: &Value) -> Option<Self> {
+        match value {
+            Value::Unit => Some(Scalar::Unit),
+            Value::Bool(b) => Some(Scalar::Bool(*b)),
+            Value::Int(n) => Some(Scalar::Int(*n)),
+            Value::Float(n) => Some(Scalar::Float(n.to_bits())),
+            Value::String(s) => Some(Scalar::String(s.clone())),
+            _ => None,
+        }
+    }
 }
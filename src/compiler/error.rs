@@ -0,0 +1,58 @@
+use std::fmt::Display;
+
+use super::*;
+
+/// A program that parses but cannot be compiled, pointing at the source
+/// when the error is tied to a place in it.
+#[derive(Debug)]
+pub struct CompileError {
+    pub msg: String,
+    /// Where the error starts and ends, with the source line where it starts.
+    pub location: Option<(Position, Position, String)>,
+}
+
+impl CompileError {
+    pub fn new(msg: String, source: &str, span: Span) -> Self {
+        let start = Position::of(source, span.start);
+        let line = source_line(source, start.line).to_string();
+        let location = Some((start, Position::of(source, span.end), line));
+        CompileError { msg, location }
+    }
+
+    /// The error, pointing at `span` unless it points somewhere already.
+    pub fn or_at(self, source: &str, span: Span) -> Self {
+        match self.location {
+            Some(_) => self,
+            None => CompileError::new(self.msg, source, span),
+        }
+    }
+
+    /// Renders the error, with a caret-underlined snippet of the source when
+    /// it has a location, pointing at `path` when the source comes from a file.
+    pub fn render(&self, path: Option<&str>) -> String {
+        match &self.location {
+            Some((start, end, line)) => {
+                let snippet = render_snippet(*start, *end, line, path);
+                format!("error: {}\n{}", self.msg, snippet)
+            }
+            None => format!("error: {}", self.msg),
+        }
+    }
+}
+
+impl From<String> for CompileError {
+    fn from(msg: String) -> Self {
+        CompileError {
+            msg,
+            location: None,
+        }
+    }
+}
+
+impl Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.render(None))
+    }
+}
+
+impl std::error::Error for CompileError {}
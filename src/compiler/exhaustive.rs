@@ -0,0 +1,144 @@
+use std::{iter, rc::Rc};
+
+use super::*;
+
+/// The patterns still to match, one per column; `None` matches anything.
+type Row<'p, 'a> = Vec<Option<&'p Pattern<'a>>>;
+
+/// A value a pattern can take apart: a variant of an enum, or a bool.
+enum Constructor {
+    Variant(Rc<EnumType>, usize),
+    Bool(bool),
+}
+
+impl Constructor {
+    fn arity(&self) -> usize {
+        match self {
+            Constructor::Variant(ty, tag) => ty.variants[*tag].fields.len(),
+            Constructor::Bool(_) => 0,
+        }
+    }
+
+    fn display(&self, fields: Vec<String>) -> String {
+        match self {
+            Constructor::Variant(ty, tag) => {
+                let name = format!("{}::{}", ty.name, ty.variants[*tag].name);
+                match fields.is_empty() {
+                    true => name,
+                    false => format!("{}({})", name, fields.join(", ")),
+                }
+            }
+            Constructor::Bool(b) => b.to_string(),
+        }
+    }
+}
+
+/// Checks that the arms of a `match` cover every value of the enum or
+/// bool they take apart, naming one that is missed.
+pub fn check_exhaustive(arms: &[Arm], chunk: &Chunk) -> Result<(), String> {
+    let rows = arms.iter().map(|arm| vec![Some(&arm.pattern)]).collect();
+    match uncovered(rows, 1, chunk)? {
+        Some(witness) => Err(format!("The match does not cover `{}`", witness.join(", "))),
+        None => Ok(()),
+    }
+}
+
+fn is_wildcard(pattern: Option<&Pattern>) -> bool {
+    matches!(pattern, None | Some(Pattern::Id(_) | Pattern::Wildcard(_)))
+}
+
+/// Values of `width` columns that no row matches, if any: one pattern per column.
+fn uncovered(rows: Vec<Row>, width: usize, chunk: &Chunk) -> Result<Option<Vec<String>>, String> {
+    if width == 0 {
+        return Ok(rows.is_empty().then(Vec::new));
+    }
+    let mut enum_type = None;
+    let mut bools = [false; 2];
+    for row in &rows {
+        match row[0] {
+            Some(Pattern::Variant {
+                enum_name,
+                variant,
+                fields,
+                ..
+            }) => {
+                let (ty, tag) = resolve_variant(enum_name, variant, chunk)?;
+                let arity = ty.variants[tag].fields.len();
+                if fields.len() != arity {
+                    return Err(format!(
+                        "Variant `{}::{}` holds {} values, but the pattern has {}",
+                        enum_name,
+                        variant,
+                        arity,
+                        fields.len()
+                    ));
+                }
+                enum_type.get_or_insert(ty);
+            }
+            Some(Pattern::Literal(Literal::Boolean(b), _)) => bools[*b as usize] = true,
+            _ => {}
+        }
+    }
+    let constructors = match (enum_type, bools) {
+        (Some(ty), _) => (0..ty.variants.len())
+            .map(|tag| Constructor::Variant(ty.clone(), tag))
+            .collect(),
+        (None, [true, true]) => vec![Constructor::Bool(false), Constructor::Bool(true)],
+        _ => Vec::new(),
+    };
+    if !constructors.is_empty() {
+        for constructor in constructors {
+            let arity = constructor.arity();
+            let specialized = (rows.iter())
+                .filter_map(|row| specialize(row, &constructor, arity))
+                .collect();
+            if let Some(mut witness) = uncovered(specialized, arity + width - 1, chunk)? {
+                let rest = witness.split_off(arity);
+                let head = constructor.display(witness);
+                return Ok(Some(iter::once(head).chain(rest).collect()));
+            }
+        }
+        return Ok(None);
+    }
+    // ints, floats and strings have too many values to list, and some bool is
+    // missing: only the rows matching anything cover the rest
+    let default = (rows.iter())
+        .filter(|row| is_wildcard(row[0]))
+        .map(|row| row[1..].to_vec())
+        .collect();
+    let head = match bools {
+        [true, false] => "true",
+        [false, true] => "false",
+        _ => "_",
+    };
+    Ok(uncovered(default, width - 1, chunk)?
+        .map(|rest| iter::once(head.to_string()).chain(rest).collect()))
+}
+
+/// The row for the values built with `constructor`: its fields replace the
+/// first column. `None` when the row cannot match such values.
+fn specialize<'p, 'a>(
+    row: &Row<'p, 'a>,
+    constructor: &Constructor,
+    arity: usize,
+) -> Option<Row<'p, 'a>> {
+    let rest = row[1..].iter().copied();
+    match (row[0], constructor) {
+        (head, _) if is_wildcard(head) => Some(iter::repeat_n(None, arity).chain(rest).collect()),
+        (
+            Some(Pattern::Variant {
+                enum_name,
+                variant,
+                fields,
+                ..
+            }),
+            Constructor::Variant(ty, tag),
+        ) if enum_name.0 == ty.name && variant.0 == ty.variants[*tag].name => {
+            Some(fields.iter().map(Some).chain(rest).collect())
+        }
+        (Some(Pattern::Literal(Literal::Boolean(b), _)), Constructor::Bool(c)) if b == c => {
+            Some(rest.collect())
+        }
+        _ => None,
+    }
+}
@@ -1,8 +1,9 @@
 use super::*;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Inst {
     // Push(isize),
-    // Pop,
+    Pop,
     Add,
     Sub,
     Mul,
@@ -10,18 +11,68 @@ pub enum Inst {
     // Incr,
     // Decr,
     Neg,
-    Const(u8),
+    Not,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    // pops two ints into a range
+    Range,
+    // pops an index and pushes the element of the value below it
+    Index,
+    // pops an end and a start bound, unit when left out, then slices the value below
+    Slice,
+    // pops a value, an index and an array, and sets the element
+    SetIndex,
+    // pops that many values into an array
+    Array(u16),
+    // pops that many values into a tuple
+    Tuple(u16),
+    // replaces a tuple with its element
+    TupleField(u8),
+    // replaces a tuple of exactly that many elements with its elements
+    Unpack(u8),
+    // pops the fields of the struct type at that constant into an instance
+    Struct(u16),
+    // replaces a struct with the field named by the constant, expected at the offset
+    GetField(u8, u16),
+    // pops a value and sets it to the field of the struct under it, popped too
+    SetField(u8, u16),
+    // pops the values of a variant, the enum at the constant and the tag, into one
+    Variant(u16, u8),
+    // replaces a value by whether it is that variant
+    IsVariant(u16, u8),
+    // replaces a variant with its value
+    VariantField(u8),
+    // pops the value no arm of a `match` covers, and fails
+    NoMatch,
+    Const(u16),
 
-    // Jump(Pointer),
-    // Je(Pointer),
-    // Jne(Pointer),
+    Jump(Pointer),
+    // jumps when the condition on top of the stack is false, leaving it there
+    JumpIfFalse(Pointer),
+    JumpIfTrue(Pointer),
+    // jumps backward to the start of a loop
+    Loop(Pointer),
+    // with an iterable and the index of the next element on top (a byte offset
+    // into a string), pushes that element and moves the index past it, or
+    // jumps when there are no more
+    ForNext(Pointer),
+    // drops that many locals
+    Drop(u16),
+    // drops the locals of a block, keeping the block's value on top
+    EndBlock(u16),
 
-    // Get(i8),
-    // Set(i8),
-    DefineGlobal(u8),
-    GetGlobal(u8),
+    Get(u16),
+    Set(u16),
+    GetUpvalue(u8),
+    Closure(u16),
+    DefineGlobal(u16),
+    GetGlobal(u16),
 
-    // Call(Pointer),
+    Call(u8),
     Ret,
 }
 
@@ -33,6 +84,54 @@ impl Inst {
             Inst::Mul => "OP_MULTIPLY".to_string(),
             Inst::Div => "OP_DIVIDE".to_string(),
             Inst::Neg => "OP_NEGATE".to_string(),
+            Inst::Not => "OP_NOT".to_string(),
+            Inst::Eq => "OP_EQUAL".to_string(),
+            Inst::Ne => "OP_NOT_EQUAL".to_string(),
+            Inst::Lt => "OP_LESS".to_string(),
+            Inst::Le => "OP_LESS_EQUAL".to_string(),
+            Inst::Gt => "OP_GREATER".to_string(),
+            Inst::Ge => "OP_GREATER_EQUAL".to_string(),
+            Inst::Range => "OP_RANGE".to_string(),
+            Inst::Index => "OP_INDEX".to_string(),
+            Inst::Slice => "OP_SLICE".to_string(),
+            Inst::SetIndex => "OP_SET_INDEX".to_string(),
+            Inst::Array(n) => format!("OP_ARRAY       {:2}", n),
+            Inst::Tuple(n) => format!("OP_TUPLE       {:2}", n),
+            Inst::TupleField(i) => format!("OP_FIELD       {:2}", i),
+            Inst::Unpack(n) => format!("OP_UNPACK      {:2}", n),
+            Inst::Variant(ind, tag) => format!(
+                "OP_VARIANT     {:2}  <{}>",
+                tag,
+                chunk.get_const(*ind as usize)
+            ),
+            Inst::IsVariant(ind, tag) => format!(
+                "OP_IS_VARIANT  {:2}  <{}>",
+                tag,
+                chunk.get_const(*ind as usize)
+            ),
+            Inst::VariantField(i) => format!("OP_VARIANT_FIELD {:2}", i),
+            Inst::NoMatch => "OP_NO_MATCH".to_string(),
+            Inst::Struct(ind) => {
+                format!(
+                    "OP_STRUCT      {:2}  <{}>",
+                    ind,
+                    chunk.get_const(*ind as usize)
+                )
+            }
+            Inst::GetField(offset, ind) => {
+                format!(
+                    "OP_GET_FIELD   {:2}  <{}>",
+                    offset,
+                    chunk.get_const(*ind as usize)
+                )
+            }
+            Inst::SetField(offset, ind) => {
+                format!(
+                    "OP_SET_FIELD   {:2}  <{}>",
+                    offset,
+                    chunk.get_const(*ind as usize)
+                )
+            }
             Inst::Const(ind) => {
                 format!(
                     "OP_CONSTANT    {:2}  <{}>",
@@ -40,6 +139,25 @@ impl Inst {
                     chunk.get_const(*ind as usize)
                 )
             }
+            Inst::Pop => "OP_POP".to_string(),
+            Inst::Jump(p) => format!("OP_JUMP        -> {:04}", p),
+            Inst::JumpIfFalse(p) => format!("OP_JUMP_FALSE  -> {:04}", p),
+            Inst::JumpIfTrue(p) => format!("OP_JUMP_TRUE   -> {:04}", p),
+            Inst::Loop(p) => format!("OP_LOOP        -> {:04}", p),
+            Inst::ForNext(p) => format!("OP_FOR_NEXT    -> {:04}", p),
+            Inst::Drop(n) => format!("OP_DROP        {:2}", n),
+            Inst::EndBlock(n) => format!("OP_END_BLOCK   {:2}", n),
+            Inst::Get(slot) => format!("OP_GET_LOCAL   {:2}", slot),
+            Inst::Set(slot) => format!("OP_SET_LOCAL   {:2}", slot),
+            Inst::GetUpvalue(ind) => format!("OP_GET_UPVALUE {:2}", ind),
+            Inst::Closure(ind) => {
+                format!(
+                    "OP_CLOSURE     {:2}  <{}>",
+                    ind,
+                    chunk.get_const(*ind as usize)
+                )
+            }
+            Inst::Call(argc) => format!("OP_CALL        {:2}", argc),
             Inst::Ret => "OP_RETURN".to_string(),
             Inst::DefineGlobal(ind) => {
                 format!(
@@ -1,34 +1,75 @@
 mod chunk;
+mod error;
+mod exhaustive;
 mod instruction;
+mod purity;
+mod scope;
+
+use std::rc::Rc;
 
 use super::*;
 
 use chunk::get_const_ind;
-pub use chunk::{disassemble_chunk, Chunk};
+pub use chunk::{disassemble_chunk, Chunk, Types};
+pub use error::CompileError;
+use exhaustive::check_exhaustive;
 pub use instruction::Inst;
+pub use purity::{check_line_purity, check_purity};
+use scope::Scope;
+
+fn compile_expr<'a>(
+    expr: Expr<'a>,
+    chunk: &mut Chunk,
+    scope: &mut Scope<'a>,
+) -> Result<(), CompileError> {
+    let outer = chunk.set_span(expr.span);
+    let result = compile_expr_kind(expr.kind, chunk, scope);
+    chunk.set_span(outer);
+    // an error found within the expression, and not more precisely, points at it
+    result.map_err(|error| error.or_at(chunk.source(), expr.span))
+}
 
-fn compile_expr(expr: Expr, chunk: &mut Chunk) -> Result<(), String> {
+fn compile_expr_kind<'a>(
+    expr: ExprKind<'a>,
+    chunk: &mut Chunk,
+    scope: &mut Scope<'a>,
+) -> Result<(), CompileError> {
     match expr {
-        Expr::Id(name) => {
-            let ind = chunk.add_constant(Value::String(name.to_string()));
-            chunk.add_inst(Inst::GetGlobal(ind));
+        ExprKind::Id(Ident(name, _)) => {
+            if let Some(slot) = scope.resolve(name) {
+                chunk.add_inst(Inst::Get(slot));
+            } else if let Some(ind) = scope.resolve_upvalue(name)? {
+                chunk.add_inst(Inst::GetUpvalue(ind));
+            } else {
+                let ind = chunk.add_string(name)?;
+                chunk.add_inst(Inst::GetGlobal(ind));
+            }
         }
-        Expr::Literal(Literal::Boolean(_b)) => {
-            // let b  = chunk.add_constant(Value::(b));
-            // chunk.add_inst(Inst::Const(b));
-            todo!()
+        ExprKind::Literal(literal) => {
+            let ind = add_literal(&literal, chunk)?;
+            chunk.add_inst(Inst::Const(ind));
         }
-        Expr::Literal(Literal::Number(n)) => {
-            let n = chunk.add_constant(Value::Number(n));
-            chunk.add_inst(Inst::Const(n));
+        ExprKind::BinOp(lhs, Infix::And, rhs) => {
+            // the lhs is the result when it is false
+            compile_expr(*lhs, chunk, scope)?;
+            let end_jump = chunk.add_jump(Inst::JumpIfFalse(0));
+            chunk.add_inst(Inst::Pop);
+            compile_expr(*rhs, chunk, scope)?;
+            chunk.patch_jump(end_jump);
         }
-        Expr::Literal(Literal::String(s)) => {
-            let ind = chunk.add_constant(Value::String(s.to_string()));
-            chunk.add_inst(Inst::Const(ind));
+        ExprKind::BinOp(lhs, Infix::Or, rhs) => {
+            // the lhs is the result when it is true
+            compile_expr(*lhs, chunk, scope)?;
+            let end_jump = chunk.add_jump(Inst::JumpIfTrue(0));
+            chunk.add_inst(Inst::Pop);
+            compile_expr(*rhs, chunk, scope)?;
+            chunk.patch_jump(end_jump);
         }
-        Expr::BinOp(lhs, op, rhs) => {
-            compile_expr(*lhs, chunk)?;
-            compile_expr(*rhs, chunk)?;
+        ExprKind::BinOp(lhs, op, rhs) => {
+            compile_expr(*lhs, chunk, scope)?;
+            scope.push_temp()?;
+            compile_expr(*rhs, chunk, scope)?;
+            scope.truncate(scope.len() - 1);
             match op {
                 Infix::Add => {
                     chunk.add_inst(Inst::Add);
@@ -42,45 +83,1574 @@ fn compile_expr(expr: Expr, chunk: &mut Chunk) -> Result<(), String> {
                 Infix::Div => {
                     chunk.add_inst(Inst::Div);
                 }
+                Infix::Eq => {
+                    chunk.add_inst(Inst::Eq);
+                }
+                Infix::Ne => {
+                    chunk.add_inst(Inst::Ne);
+                }
+                Infix::Lt => {
+                    chunk.add_inst(Inst::Lt);
+                }
+                Infix::Le => {
+                    chunk.add_inst(Inst::Le);
+                }
+                Infix::Gt => {
+                    chunk.add_inst(Inst::Gt);
+                }
+                Infix::Ge => {
+                    chunk.add_inst(Inst::Ge);
+                }
+                Infix::Range => {
+                    chunk.add_inst(Inst::Range);
+                }
+                Infix::And | Infix::Or => unreachable!("short-circuit operators compiled above"),
             }
         }
-        Expr::Prefix(op, rhs) => {
-            compile_expr(*rhs, chunk)?;
+        ExprKind::Prefix(op, rhs) => {
+            compile_expr(*rhs, chunk, scope)?;
             match op {
                 Prefix::Neg => {
                     chunk.add_inst(Inst::Neg);
                 }
+                Prefix::Not => {
+                    chunk.add_inst(Inst::Not);
+                }
+            }
+        }
+        ExprKind::Fn(function) => compile_closure(function, "anonymous", chunk, scope)?,
+        ExprKind::Call { func, args } => {
+            if let ExprKind::Path(enum_name, variant) = func.kind {
+                return compile_variant(enum_name, variant, args, chunk, scope);
+            }
+            let argc = u8::try_from(args.len())
+                .map_err(|_| format!("Too many arguments in call to {}", func))?;
+            let mark = scope.len();
+            compile_expr(*func, chunk, scope)?;
+            scope.push_temp()?;
+            for arg in args {
+                compile_expr(arg, chunk, scope)?;
+                scope.push_temp()?;
+            }
+            scope.truncate(mark);
+            chunk.add_inst(Inst::Call(argc));
+        }
+        ExprKind::If { cond, then, els } => {
+            compile_expr(*cond, chunk, scope)?;
+            let then_jump = chunk.add_jump(Inst::JumpIfFalse(0));
+            chunk.add_inst(Inst::Pop);
+            compile_block(then, chunk, scope)?;
+            let else_jump = chunk.add_jump(Inst::Jump(0));
+            chunk.patch_jump(then_jump);
+            chunk.add_inst(Inst::Pop);
+            match els {
+                Some(els) => compile_block(els, chunk, scope)?,
+                None => add_unit(chunk)?,
+            }
+            chunk.patch_jump(else_jump);
+        }
+        ExprKind::Tuple(elements) => {
+            let len = compile_elements(elements, "a tuple", chunk, scope)?;
+            chunk.add_inst(Inst::Tuple(len));
+        }
+        ExprKind::Array(elements) => {
+            let len = compile_elements(elements, "an array", chunk, scope)?;
+            chunk.add_inst(Inst::Array(len));
+        }
+        ExprKind::TupleField { expr, index } => {
+            let index =
+                u8::try_from(index).map_err(|_| format!("Tuple field {} out of range", index))?;
+            compile_expr(*expr, chunk, scope)?;
+            chunk.add_inst(Inst::TupleField(index));
+        }
+        ExprKind::Path(enum_name, variant) => {
+            compile_variant(enum_name, variant, Vec::new(), chunk, scope)?
+        }
+        ExprKind::Match { expr, arms } => {
+            check_exhaustive(&arms, chunk)?;
+            let mark = scope.len();
+            compile_expr(*expr, chunk, scope)?;
+            scope.push_temp()?;
+            let slot = (scope.len() - 1) as u16;
+            // each arm tests its pattern, then binds it and runs its body;
+            // a failed test jumps to the next arm
+            let mut end_jumps = Vec::new();
+            for Arm { pattern, body } in arms {
+                let outer = chunk.set_span(pattern.span());
+                let mut fails = Vec::new();
+                test_pattern(&pattern, slot, &mut Vec::new(), chunk, &mut fails)
+                    .map_err(|msg| CompileError::new(msg, chunk.source(), pattern.span()))?;
+                let locals = scope.len();
+                bind_match(&pattern, slot, &mut Vec::new(), chunk, scope)?;
+                chunk.set_span(outer);
+                compile_block(body, chunk, scope)?;
+                end_block(locals, chunk, scope)?;
+                end_jumps.push(chunk.add_jump(Inst::Jump(0)));
+                if !fails.is_empty() {
+                    for jump in fails {
+                        chunk.patch_jump(jump);
+                    }
+                    chunk.add_inst(Inst::Pop);
+                }
+            }
+            // only reached by values of another type than the patterns
+            chunk.add_inst(Inst::Get(slot));
+            chunk.add_inst(Inst::NoMatch);
+            for jump in end_jumps {
+                chunk.patch_jump(jump);
+            }
+            end_block(mark, chunk, scope)?;
+        }
+        ExprKind::Field { expr, field } => {
+            let (offset, name) = resolve_field(field.0, chunk)?;
+            compile_expr(*expr, chunk, scope)?;
+            chunk.add_inst(Inst::GetField(offset, name));
+        }
+        ExprKind::Struct { name, fields } => {
+            let ty = chunk
+                .get_struct(name.0)
+                .ok_or_else(|| format!("Unknown struct `{}`", name))?;
+            let mut values: Vec<Option<Expr>> = ty.fields.iter().map(|_| None).collect();
+            for (field, value) in fields {
+                let offset = ty
+                    .fields
+                    .iter()
+                    .position(|f| **f == *field.0)
+                    .ok_or_else(|| format!("Struct `{}` has no field `{}`", name, field))?;
+                if values[offset].replace(value).is_some() {
+                    return Err(format!("Field `{}` of `{}` is given twice", field, name).into());
+                }
+            }
+            // the fields are evaluated in the order of the declaration
+            let mark = scope.len();
+            for (value, field) in values.into_iter().zip(ty.fields.iter()) {
+                let value = value
+                    .ok_or_else(|| format!("Missing field `{}` of struct `{}`", field, name))?;
+                compile_expr(value, chunk, scope)?;
+                scope.push_temp()?;
+            }
+            scope.truncate(mark);
+            let ind = chunk.add_constant(Value::StructType(ty))?;
+            chunk.add_inst(Inst::Struct(ind));
+        }
+        ExprKind::Index { expr, index } => {
+            compile_expr(*expr, chunk, scope)?;
+            scope.push_temp()?;
+            compile_expr(*index, chunk, scope)?;
+            scope.truncate(scope.len() - 1);
+            chunk.add_inst(Inst::Index);
+        }
+        ExprKind::Slice { expr, start, end } => {
+            let mark = scope.len();
+            compile_expr(*expr, chunk, scope)?;
+            scope.push_temp()?;
+            for bound in [start, end] {
+                match bound {
+                    Some(bound) => compile_expr(*bound, chunk, scope)?,
+                    None => add_unit(chunk)?,
+                }
+                scope.push_temp()?;
+            }
+            scope.truncate(mark);
+            chunk.add_inst(Inst::Slice);
+        }
+    }
+    Ok(())
+}
+
+fn add_literal(literal: &Literal, chunk: &mut Chunk) -> Result<u16, String> {
+    match literal {
+        Literal::Boolean(b) => chunk.add_constant(Value::Bool(*b)),
+        Literal::Int(n) => chunk.add_constant(Value::Int(*n)),
+        Literal::Float(n) => chunk.add_constant(Value::Float(*n)),
+        Literal::String(s) => chunk.add_string(s),
+    }
+}
+
+/// The enum of `Enum::Variant` and the tag of the variant.
+fn resolve_variant(
+    enum_name: &Ident,
+    variant: &Ident,
+    chunk: &Chunk,
+) -> Result<(Rc<EnumType>, usize), String> {
+    let ty = chunk
+        .get_enum(enum_name.0)
+        .ok_or_else(|| format!("Unknown enum `{}`", enum_name))?;
+    let tag = (ty.variants.iter())
+        .position(|v| v.name == variant.0)
+        .ok_or_else(|| format!("Enum `{}` has no variant `{}`", enum_name, variant))?;
+    Ok((ty, tag))
+}
+
+/// Builds a variant from the values it holds.
+fn compile_variant<'a>(
+    enum_name: Ident<'a>,
+    variant: Ident<'a>,
+    values: Vec<Expr<'a>>,
+    chunk: &mut Chunk,
+    scope: &mut Scope<'a>,
+) -> Result<(), CompileError> {
+    let (ty, tag) = resolve_variant(&enum_name, &variant, chunk)?;
+    let arity = ty.variants[tag].fields.len();
+    if values.len() != arity {
+        return Err(format!(
+            "Variant `{}::{}` holds {} values, but {} were given",
+            enum_name,
+            variant,
+            arity,
+            values.len()
+        )
+        .into());
+    }
+    compile_elements(values, "a variant", chunk, scope)?;
+    let ind = chunk.add_constant(Value::EnumType(ty))?;
+    // an enum has at most 256 variants
+    chunk.add_inst(Inst::Variant(ind, tag as u8));
+    Ok(())
+}
+
+/// Pushes the part of the value in `slot` that `path` leads to, through the
+/// values of nested variants.
+fn load_path(slot: u16, path: &[u8], chunk: &mut Chunk) {
+    chunk.add_inst(Inst::Get(slot));
+    for &i in path {
+        chunk.add_inst(Inst::VariantField(i));
+    }
+}
+
+/// Tests the value in `slot` against a pattern of a `match`, adding the
+/// jumps taken when it does not match to `fails`. They leave a `false` on
+/// the stack.
+fn test_pattern(
+    pattern: &Pattern,
+    slot: u16,
+    path: &mut Vec<u8>,
+    chunk: &mut Chunk,
+    fails: &mut Vec<usize>,
+) -> Result<(), String> {
+    match pattern {
+        Pattern::Id(_) | Pattern::Wildcard(_) => {}
+        Pattern::Literal(literal, _) => {
+            load_path(slot, path, chunk);
+            let ind = add_literal(literal, chunk)?;
+            chunk.add_inst(Inst::Const(ind));
+            chunk.add_inst(Inst::Eq);
+            fails.push(chunk.add_jump(Inst::JumpIfFalse(0)));
+            chunk.add_inst(Inst::Pop);
+        }
+        Pattern::Variant {
+            enum_name,
+            variant,
+            fields,
+            ..
+        } => {
+            let (ty, tag) = resolve_variant(enum_name, variant, chunk)?;
+            load_path(slot, path, chunk);
+            let ind = chunk.add_constant(Value::EnumType(ty))?;
+            chunk.add_inst(Inst::IsVariant(ind, tag as u8));
+            fails.push(chunk.add_jump(Inst::JumpIfFalse(0)));
+            chunk.add_inst(Inst::Pop);
+            for (i, field) in fields.iter().enumerate() {
+                path.push(i as u8);
+                test_pattern(field, slot, path, chunk, fails)?;
+                path.pop();
+            }
+        }
+        Pattern::Tuple(..) => return Err("A `match` cannot take tuples apart".to_string()),
+    }
+    Ok(())
+}
+
+/// Declares the variables of a pattern that matched the value in `slot`.
+fn bind_match<'a>(
+    pattern: &Pattern<'a>,
+    slot: u16,
+    path: &mut Vec<u8>,
+    chunk: &mut Chunk,
+    scope: &mut Scope<'a>,
+) -> Result<(), String> {
+    match pattern {
+        Pattern::Id(Ident(name, _)) => {
+            load_path(slot, path, chunk);
+            scope.declare(name)?;
+        }
+        Pattern::Variant { fields, .. } => {
+            for (i, field) in fields.iter().enumerate() {
+                path.push(i as u8);
+                bind_match(field, slot, path, chunk, scope)?;
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// The offset the field is expected at, and the constant of its name.
+fn resolve_field(field: &str, chunk: &mut Chunk) -> Result<(u8, u16), String> {
+    let offset = chunk
+        .field_offset(field)
+        .ok_or_else(|| format!("No struct has a field `{}`", field))?;
+    // a struct has at most 256 fields
+    Ok((offset as u8, chunk.add_string(field)?))
+}
+
+/// Pushes the elements of a tuple or array literal, returning how many there are.
+fn compile_elements<'a>(
+    elements: Vec<Expr<'a>>,
+    what: &str,
+    chunk: &mut Chunk,
+    scope: &mut Scope<'a>,
+) -> Result<u16, CompileError> {
+    let len = u16::try_from(elements.len())
+        .map_err(|_| format!("Too many elements in {} literal", what))?;
+    let mark = scope.len();
+    for element in elements {
+        compile_expr(element, chunk, scope)?;
+        scope.push_temp()?;
+    }
+    scope.truncate(mark);
+    Ok(len)
+}
+
+fn compile_statement<'a>(
+    statement: Statement<'a>,
+    chunk: &mut Chunk,
+    scope: &mut Scope<'a>,
+) -> Result<(), CompileError> {
+    let outer = chunk.set_span(statement.span);
+    let result = compile_statement_kind(statement.kind, chunk, scope);
+    chunk.set_span(outer);
+    result.map_err(|error| error.or_at(chunk.source(), statement.span))
+}
+
+fn compile_statement_kind<'a>(
+    statement: StatementKind<'a>,
+    chunk: &mut Chunk,
+    scope: &mut Scope<'a>,
+) -> Result<(), CompileError> {
+    match statement {
+        StatementKind::Let(Pattern::Id(Ident(name, _)), _, expr) => match *expr {
+            // declared first, so that the function can call itself
+            Expr {
+                kind: ExprKind::Fn(function),
+                span,
+            } => {
+                scope.declare(name)?;
+                let outer = chunk.set_span(span);
+                compile_closure(function, name, chunk, scope)?;
+                chunk.set_span(outer);
+            }
+            // the value is left on the stack, right in the new local's slot
+            expr => {
+                compile_expr(expr, chunk, scope)?;
+                scope.declare(name)?;
+            }
+        },
+        StatementKind::Let(pattern, _, expr) => {
+            compile_expr(*expr, chunk, scope)?;
+            bind_pattern(pattern, chunk, scope)?;
+        }
+        StatementKind::Set(Ident(name, _), expr) => {
+            compile_expr(*expr, chunk, scope)?;
+            if let Some(slot) = scope.resolve(name) {
+                chunk.add_inst(Inst::Set(slot));
+            } else {
+                return Err(format!("Cannot assign to `{}`: not a local variable.", name).into());
+            }
+        }
+        StatementKind::SetIndex {
+            target,
+            index,
+            value,
+        } => {
+            let mark = scope.len();
+            compile_expr(*target, chunk, scope)?;
+            scope.push_temp()?;
+            compile_expr(*index, chunk, scope)?;
+            scope.push_temp()?;
+            compile_expr(*value, chunk, scope)?;
+            scope.truncate(mark);
+            chunk.add_inst(Inst::SetIndex);
+        }
+        StatementKind::SetField {
+            target,
+            field,
+            value,
+        } => {
+            let (offset, name) = resolve_field(field.0, chunk)?;
+            let mark = scope.len();
+            compile_expr(*target, chunk, scope)?;
+            scope.push_temp()?;
+            compile_expr(*value, chunk, scope)?;
+            scope.truncate(mark);
+            chunk.add_inst(Inst::SetField(offset, name));
+        }
+        StatementKind::Return(expr) => {
+            compile_expr(*expr, chunk, scope)?;
+            chunk.add_inst(Inst::Ret);
+        }
+        StatementKind::Expr(expr) => {
+            compile_expr(*expr, chunk, scope)?;
+            chunk.add_inst(Inst::Pop);
+        }
+        StatementKind::While { cond, body } => {
+            let start = chunk.len();
+            compile_expr(*cond, chunk, scope)?;
+            let exit_jump = chunk.add_jump(Inst::JumpIfFalse(0));
+            chunk.add_inst(Inst::Pop);
+            scope.begin_loop(start);
+            compile_block(body, chunk, scope)?;
+            chunk.add_inst(Inst::Pop);
+            chunk.add_inst(Inst::Loop(start));
+            chunk.patch_jump(exit_jump);
+            chunk.add_inst(Inst::Pop);
+            // a `break` already popped the condition
+            for jump in scope.end_loop() {
+                chunk.patch_jump(jump);
+            }
+        }
+        StatementKind::For {
+            pattern,
+            iterable,
+            body,
+        } => {
+            // the iterable and the index of its next element stay under the body
+            let mark = scope.len();
+            compile_expr(*iterable, chunk, scope)?;
+            scope.push_temp()?;
+            let zero = chunk.add_constant(Value::Int(0))?;
+            chunk.add_inst(Inst::Const(zero));
+            scope.push_temp()?;
+            let start = chunk.len();
+            let exit_jump = chunk.add_jump(Inst::ForNext(0));
+            scope.begin_loop(start);
+            bind_pattern(pattern, chunk, scope)?;
+            compile_block(body, chunk, scope)?;
+            chunk.add_inst(Inst::Pop);
+            let (_, depth) = scope.current_loop().unwrap();
+            add_drop(scope.truncate(depth), chunk);
+            chunk.add_inst(Inst::Loop(start));
+            chunk.patch_jump(exit_jump);
+            for jump in scope.end_loop() {
+                chunk.patch_jump(jump);
             }
+            add_drop(scope.truncate(mark), chunk);
+        }
+        StatementKind::Break => {
+            let (_, depth) = scope
+                .current_loop()
+                .ok_or_else(|| "`break` outside of a loop".to_string())?;
+            add_drop(scope.len() - depth, chunk);
+            let jump = chunk.add_jump(Inst::Jump(0));
+            scope.add_break(jump);
         }
-        Expr::Fn(_) => {
-            todo!()
+        StatementKind::Continue => {
+            let (start, depth) = scope
+                .current_loop()
+                .ok_or_else(|| "`continue` outside of a loop".to_string())?;
+            add_drop(scope.len() - depth, chunk);
+            chunk.add_inst(Inst::Loop(start));
+        }
+    }
+    Ok(())
+}
+
+/// Drops the `n` locals on top of the stack, if any.
+fn add_drop(n: usize, chunk: &mut Chunk) {
+    if n > 0 {
+        // the scope keeps the number of slots within a `u16`
+        chunk.add_inst(Inst::Drop(n as u16));
+    }
+}
+
+/// Binds the value on top of the stack, which sits in the next local slot.
+fn bind_pattern<'a>(
+    pattern: Pattern<'a>,
+    chunk: &mut Chunk,
+    scope: &mut Scope<'a>,
+) -> Result<(), String> {
+    let (patterns, span) = match pattern {
+        Pattern::Id(Ident(name, _)) => return scope.declare(name).map(|_| ()),
+        Pattern::Wildcard(_) => return scope.push_temp(),
+        Pattern::Tuple(patterns, span) => (patterns, span),
+        pattern => {
+            return Err(format!(
+                "Pattern `{}` may not match: use a `match`",
+                pattern
+            ))
         }
-        Expr::Call { .. } => {
-            todo!()
+    };
+    let len = u8::try_from(patterns.len())
+        .map_err(|_| "Too many elements in a tuple pattern".to_string())?;
+    let outer = chunk.set_span(span);
+    chunk.add_inst(Inst::Unpack(len));
+    chunk.set_span(outer);
+    // the elements take one slot each, nested tuples are kept as temporaries
+    // and taken apart once all the slots are there
+    let mut nested = Vec::new();
+    for pattern in patterns {
+        match pattern {
+            Pattern::Id(Ident(name, _)) => {
+                scope.declare(name)?;
+            }
+            Pattern::Wildcard(_) => scope.push_temp()?,
+            pattern => {
+                nested.push((scope.len() as u16, pattern));
+                scope.push_temp()?;
+            }
         }
     }
+    for (slot, pattern) in nested {
+        chunk.add_inst(Inst::Get(slot));
+        bind_pattern(pattern, chunk, scope)?;
+    }
+    Ok(())
+}
+
+/// Compiles a block into code leaving only its value on the stack.
+fn compile_block<'a>(
+    block: Block<'a>,
+    chunk: &mut Chunk,
+    scope: &mut Scope<'a>,
+) -> Result<(), CompileError> {
+    let mark = scope.len();
+    for statement in block.body {
+        compile_statement(statement, chunk, scope)?;
+    }
+    match block.value {
+        Some(value) => compile_expr(*value, chunk, scope)?,
+        None => add_unit(chunk)?,
+    }
+    Ok(end_block(mark, chunk, scope)?)
+}
+
+/// Drops the locals above `mark`, from under the value on top of the stack.
+fn end_block(mark: usize, chunk: &mut Chunk, scope: &mut Scope) -> Result<(), String> {
+    let dropped = scope.truncate(mark);
+    if dropped > 0 {
+        // the scope keeps the number of slots within a `u16`
+        chunk.add_inst(Inst::EndBlock(dropped as u16));
+    }
+    Ok(())
+}
+
+fn add_unit(chunk: &mut Chunk) -> Result<(), String> {
+    let unit = chunk.add_constant(Value::Unit)?;
+    chunk.add_inst(Inst::Const(unit));
+    Ok(())
+}
+
+/// Compiles a function body into its own chunk.
+/// Parameters occupy the first local slots of the call frame, in order.
+fn compile_function<'a>(
+    function: Function<'a>,
+    name: &str,
+    parent: &Chunk,
+    scope: &mut Scope<'a>,
+) -> Result<FunctionObj, CompileError> {
+    let Function { params, body, .. } = function;
+    scope.begin_function();
+    let chunk = compile_body(params, body, parent.child(), scope);
+    let upvalues = scope.end_function();
+    let (arity, chunk) = chunk?;
+    Ok(FunctionObj {
+        name: name.to_string(),
+        arity,
+        chunk,
+        upvalues,
+    })
+}
+
+fn compile_body<'a>(
+    params: Vec<Param<'a>>,
+    body: Vec<Statement<'a>>,
+    mut chunk: Chunk,
+    scope: &mut Scope<'a>,
+) -> Result<(usize, Chunk), CompileError> {
+    for Param { ident, .. } in params {
+        scope.declare(ident.0)?;
+    }
+    let arity = scope.len();
+    for statement in body {
+        compile_statement(statement, &mut chunk, scope)?;
+    }
+    // falling off the end of the body returns unit
+    add_unit(&mut chunk)?;
+    chunk.add_inst(Inst::Ret);
+    Ok((arity, chunk))
+}
+
+/// Emits the code building a closure of `function` on top of the stack.
+fn compile_closure<'a>(
+    function: Function<'a>,
+    name: &str,
+    chunk: &mut Chunk,
+    scope: &mut Scope<'a>,
+) -> Result<(), CompileError> {
+    let function = compile_function(function, name, chunk, scope)?;
+    let ind = chunk.add_constant(Value::Function(Rc::new(function)))?;
+    chunk.add_inst(Inst::Closure(ind));
     Ok(())
 }
 
-fn compile_item(item: Item, chunk: &mut Chunk) -> Result<(), String> {
+fn compile_item(item: Item, chunk: &mut Chunk) -> Result<(), CompileError> {
+    let span = item.span;
+    let outer = chunk.set_span(span);
+    let result = compile_item_kind(item.kind, chunk);
+    chunk.set_span(outer);
+    result.map_err(|error| error.or_at(chunk.source(), span))
+}
+
+fn compile_item_kind(item: ItemKind, chunk: &mut Chunk) -> Result<(), CompileError> {
     match item {
-        Item::DefItem { ident, expr } => {
-            compile_expr(expr, chunk)?;
-            let ind = chunk.add_constant(Value::String(ident.to_string()));
+        ItemKind::DefItem {
+            ident,
+            expr:
+                Expr {
+                    kind: ExprKind::Fn(function),
+                    span,
+                },
+            ..
+        } => {
+            let outer = chunk.set_span(span);
+            compile_closure(function, ident.0, chunk, &mut Scope::new())?;
+            chunk.set_span(outer);
+            let ind = chunk.add_string(ident.0)?;
+            chunk.add_inst(Inst::DefineGlobal(ind));
+        }
+        ItemKind::DefItem { ident, expr, .. } => {
+            compile_expr(expr, chunk, &mut Scope::new())?;
+            let ind = chunk.add_string(ident.0)?;
             chunk.add_inst(Inst::DefineGlobal(ind));
         }
+        ItemKind::Struct { ident, fields, .. } => {
+            let fields: Vec<_> = fields.iter().map(|field| field.ident.0).collect();
+            if u8::try_from(fields.len()).is_err() {
+                return Err(format!("Too many fields in struct `{}`", ident).into());
+            }
+            for (i, field) in fields.iter().enumerate() {
+                if fields[..i].contains(field) {
+                    return Err(
+                        format!("Field `{}` of `{}` is declared twice", field, ident).into(),
+                    );
+                }
+            }
+            chunk.add_struct(ident.0, &fields)?;
+        }
+        ItemKind::Enum { ident, variants } => {
+            if variants.len() > 256 {
+                return Err(format!("Too many variants in enum `{}`", ident).into());
+            }
+            let mut types: Vec<VariantType> = Vec::new();
+            for Variant {
+                ident: name,
+                fields,
+            } in variants
+            {
+                if types.iter().any(|v| v.name == name.0) {
+                    return Err(
+                        format!("Variant `{}` of `{}` is declared twice", name, ident).into(),
+                    );
+                }
+                if u8::try_from(fields.len()).is_err() {
+                    return Err(format!("Too many values in variant `{}::{}`", ident, name).into());
+                }
+                types.push(VariantType {
+                    name: name.0.to_string(),
+                    fields: fields.iter().map(|field| field.0.to_string()).collect(),
+                });
+            }
+            chunk.add_enum(EnumType {
+                name: ident.0.to_string(),
+                variants: types,
+            })?;
+        }
     };
     Ok(())
 }
-pub fn compile(program: Program) -> Result<Chunk, String> {
-    let mut chunk = Chunk::new();
-    for item in program.0 {
-        compile_item(item, &mut chunk)?;
+
+/// Compiles a REPL line. Items evaluate to unit, expressions to their value.
+/// `types` holds the types declared by the previous lines.
+pub fn compile_line(line: Line, source: &str, types: &Types) -> Result<Chunk, CompileError> {
+    check_line_purity(&line, source)?;
+    let mut chunk = Chunk::with_types(Rc::from(source), types);
+    match line {
+        Line::Item(item) => {
+            compile_item(item, &mut chunk)?;
+            add_unit(&mut chunk)?;
+        }
+        Line::Expr(expr) => compile_expr(expr, &mut chunk, &mut Scope::new())?,
+    }
+    chunk.add_inst(Inst::Ret);
+    Ok(chunk)
+}
+
+/// Compiles the items of a program into code defining their globals.
+/// It evaluates to unit, nothing is called.
+pub fn compile_module(
+    program: Program,
+    source: &str,
+    types: &Types,
+) -> Result<Chunk, CompileError> {
+    check_purity(&program, source)?;
+    let mut chunk = Chunk::with_types(Rc::from(source), types);
+    compile_items(program, &mut chunk)?;
+    add_unit(&mut chunk)?;
+    chunk.add_inst(Inst::Ret);
+    Ok(chunk)
+}
+
+// types are declared first, so that any function can use them
+fn compile_items(program: Program, chunk: &mut Chunk) -> Result<(), CompileError> {
+    let (types, items): (Vec<_>, Vec<_>) = (program.0.into_iter())
+        .partition(|item| matches!(item.kind, ItemKind::Struct { .. } | ItemKind::Enum { .. }));
+    for item in types.into_iter().chain(items) {
+        compile_item(item, chunk)?;
     }
-    if let Some(ind) = get_const_ind(&chunk, &Value::String("main".to_string())) {
+    Ok(())
+}
+
+pub fn compile(program: Program, source: &str) -> Result<Chunk, CompileError> {
+    check_purity(&program, source)?;
+    let mut chunk = Chunk::with_source(Rc::from(source));
+    compile_items(program, &mut chunk)?;
+    if let Some(ind) = get_const_ind(&chunk, &Value::String(Rc::from("main"))) {
         chunk.add_inst(Inst::GetGlobal(ind));
+        chunk.add_inst(Inst::Ret);
         Ok(chunk)
     } else {
-        Err("No main entry found.".to_string())
+        Err("No main entry found.".to_string().into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(input: &str) -> Result<Value, RuntimeError> {
+        let program = parse_file(input).unwrap();
+        let chunk = compile(program, input).unwrap();
+        VM::new().run(chunk).map_err(|traceback| traceback.error)
+    }
+
+    #[test]
+    fn test_call_function() {
+        let result = run(r#"let add = fn(a, b) { a + b }; let main = add(1, 2);"#);
+        assert_eq!(result.unwrap(), Value::Int(3));
+    }
+
+    #[test]
+    fn test_nested_calls() {
+        let input = r#"
+let double = fn(x) { return x * 2; };
+let quad = fn(x) { double(double(x)) };
+let main = quad(3) - double(1);"#;
+        assert_eq!(run(input).unwrap(), Value::Int(10));
+    }
+
+    #[test]
+    fn test_first_class_function() {
+        let input = r#"
+let apply = fn(f, x) { f(x) };
+let main = apply(fn(n) { -n }, 5);"#;
+        assert_eq!(run(input).unwrap(), Value::Int(-5));
+    }
+
+    #[test]
+    fn test_return_to_caller() {
+        let input = r#"
+let first = fn(a, b) { return a; return b; };
+let main = first(1, 2) + 10;"#;
+        assert_eq!(run(input).unwrap(), Value::Int(11));
+    }
+
+    #[test]
+    fn test_unit_function() {
+        let result = run(r#"let nothing = fn() { }; let main = nothing();"#);
+        assert_eq!(result.unwrap(), Value::Unit);
+    }
+
+    #[test]
+    fn test_local_variables() {
+        let input = r#"
+let main = fn() {
+    let a = 1;
+    let b = a + 1;
+    a = b * 10;
+    a + b
+}();"#;
+        assert_eq!(run(input).unwrap(), Value::Int(22));
+    }
+
+    #[test]
+    fn test_shadowing() {
+        let input = r#"
+let f = fn(a, b) {
+    let a = b;
+    a = a + 1;
+    let b = a * b;
+    b
+};
+let main = f(1, 2);"#;
+        assert_eq!(run(input).unwrap(), Value::Int(6));
+        // the initializer still sees the shadowed local
+        let input = r#"let f = fn(a) { let a = a + 1; a }; let main = f(1);"#;
+        assert_eq!(run(input).unwrap(), Value::Int(2));
+    }
+
+    #[test]
+    fn test_closure_captures_by_reference() {
+        let input = r#"
+let main = fn() {
+    let x = 1;
+    let get = fn() { x };
+    x = 5;
+    get()
+}();"#;
+        assert_eq!(run(input).unwrap(), Value::Int(5));
+    }
+
+    #[test]
+    fn test_counter_factory() {
+        // each counter sees its own count, as its factory last set it
+        let input = r#"
+let make_counter = fn(start) {
+    let count = start;
+    let get = fn() { count };
+    count = count + 1;
+    get
+};
+let count_twice = fn(counter) { counter() + counter() };
+let main = fn() {
+    let a = make_counter(0);
+    let b = make_counter(10);
+    count_twice(a) * 100 + b()
+}();"#;
+        assert_eq!(run(input).unwrap(), Value::Int(211));
+    }
+
+    #[test]
+    fn test_assign_captured_rejected() {
+        for input in [
+            r#"
+let make_counter = fn() {
+    let count = 0;
+    fn() {
+        count = count + 1;
+        count
+    }
+};"#,
+            r#"
+let main = fn() {
+    let x = 1;
+    let middle = fn() {
+        fn() {
+            x = x * 10;
+            x
+        }
+    };
+    middle()()
+}();"#,
+        ] {
+            let program = parse_file(input).unwrap();
+            let error = compile(program, input).unwrap_err();
+            assert!(
+                error.msg.ends_with("it is not declared in this function"),
+                "{}",
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn test_purity() {
+        let compile_error = |input: &str| {
+            let program = parse_file(input).unwrap();
+            let error = compile(program, input).unwrap_err();
+            (error.msg, error.location.map(|(start, ..)| start))
+        };
+        let input = "let make = fn() {\n    let n = 0;\n    fn() { n = n + 1; n }\n};";
+        let (msg, at) = compile_error(input);
+        assert_eq!(
+            msg,
+            "Cannot assign to `n`: it is not declared in this function"
+        );
+        assert_eq!(at, Some(Position { line: 3, col: 12 }));
+
+        let (msg, _) = compile_error("let total = 0; let main = fn() { total = 1; };");
+        assert_eq!(
+            msg,
+            "Cannot assign to `total`: it is not declared in this function"
+        );
+
+        // what a function declares, even in a nested block, is its own
+        let input = r#"
+let main = fn(n) {
+    let acc = 0;
+    for i in 0..n { let sq = i * i; sq = sq + 1; acc = acc + sq; };
+    n = acc;
+    match n { m => { m = m + 1; m } }
+}(3);"#;
+        assert_eq!(run(input).unwrap(), Value::Int(9));
+
+        // arrays and structs are shared, so what they hold may change
+        let input = r#"
+struct P { x }
+let g = [0];
+let p = P { x: 0 };
+let set = fn() { g[0] = 1; p.x = 2; };
+let main = fn() { set(); g[0] + p.x }();"#;
+        assert_eq!(run(input).unwrap(), Value::Int(3));
+    }
+
+    #[test]
+    fn test_io_effect() {
+        let input = r#"
+let greet = fn(name) { println("hello", name); };
+let twice = fn(name) { greet(name); greet(name); };
+let ask = fn() { let line = read_line(); line };
+let sq = fn(x) { x * x };
+let shadow = fn() { let print = fn(x) { x }; print(1) };
+let alias = fn() { let p = println; p("x"); 1 };
+let log = println;
+let logged = fn() { log("x") };
+let each = fn(f) { f(1) };
+let passed = fn() { each(greet) };
+let main = twice("you");"#;
+        let program = parse_file(input).unwrap();
+        let mut io: Vec<_> = check_purity(&program, input).unwrap().into_iter().collect();
+        io.sort();
+        assert_eq!(io, ["alias", "ask", "greet", "logged", "passed", "twice"]);
+    }
+
+    #[test]
+    fn test_nested_captures() {
+        // three levels deep, after every enclosing frame has returned
+        let input = r#"
+let outer = fn(a) {
+    fn(b) {
+        fn(c) { a * 100 + b * 10 + c }
+    }
+};
+let main = outer(1)(2)(3);"#;
+        assert_eq!(run(input).unwrap(), Value::Int(123));
+        // the innermost function sees a variable two levels up as it is updated
+        let input = r#"
+let main = fn() {
+    let x = 1;
+    let middle = fn() {
+        fn() { x * 10 }
+    };
+    let inner = middle();
+    x = x * 10;
+    let a = inner();
+    x = x + 1;
+    inner() + a
+}();"#;
+        assert_eq!(run(input).unwrap(), Value::Int(210));
+    }
+
+    #[test]
+    fn test_local_recursion() {
+        let input = r#"
+let main = fn() {
+    let count_down = fn(n) { count_down };
+    count_down(3)(2)(1)
+}();"#;
+        assert!(matches!(run(input).unwrap(), Value::Closure(_)));
+    }
+
+    #[test]
+    fn test_if_expression() {
+        let input = r#"
+let pick = fn(a, b) {
+    if a { 1 } else if b { 2 } else { 3 }
+};
+let main = pick(false, true) * 100 + pick(true, false) * 10 + pick(false, false);"#;
+        assert_eq!(run(input).unwrap(), Value::Int(213));
+        let result = run(r#"let main = if false { 1 };"#);
+        assert_eq!(result.unwrap(), Value::Unit);
+        let result = run(r#"let main = if 1 { 1 };"#);
+        assert!(matches!(result, Err(RuntimeError::TypeMismatch(_))));
+    }
+
+    #[test]
+    fn test_comparison() {
+        let input = r#"
+let main = fn() {
+    let a = 1 < 2;
+    let b = 2 <= 2;
+    let c = 3 > 4;
+    let d = 4 >= 5;
+    let e = 1 + 1 == 2;
+    let f = true != false;
+    a == b && (c || d) == false && e && f
+}();"#;
+        assert_eq!(run(input).unwrap(), Value::Bool(true));
+        // equality works across types, ordering does not
+        assert_eq!(run(r#"let main = 1 == true;"#).unwrap(), Value::Bool(false));
+        let result = run(r#"let main = 1 < true;"#);
+        assert!(matches!(result, Err(RuntimeError::TypeMismatch(_))));
+    }
+
+    #[test]
+    fn test_logical_operators() {
+        assert_eq!(run(r#"let main = !false;"#).unwrap(), Value::Bool(true));
+        assert_eq!(run(r#"let main = !!true;"#).unwrap(), Value::Bool(true));
+        assert_eq!(
+            run(r#"let main = true && false;"#).unwrap(),
+            Value::Bool(false)
+        );
+        assert_eq!(
+            run(r#"let main = false || true;"#).unwrap(),
+            Value::Bool(true)
+        );
+        let result = run(r#"let main = !1;"#);
+        assert!(matches!(result, Err(RuntimeError::TypeMismatch(_))));
+    }
+
+    #[test]
+    fn test_short_circuit() {
+        // the right hand side would fail if it ran
+        let input = r#"let main = false && missing() || true || missing();"#;
+        assert_eq!(run(input).unwrap(), Value::Bool(true));
+        let result = run(r#"let main = true && missing();"#);
+        assert!(matches!(result, Err(RuntimeError::UndefinedVariable(_))));
+    }
+
+    #[test]
+    fn test_integer_arithmetic() {
+        assert_eq!(run(r#"let main = 7 / 2;"#).unwrap(), Value::Int(3));
+        assert_eq!(run(r#"let main = -7 / 2;"#).unwrap(), Value::Int(-3));
+        // beyond 2^24 and 2^53, where floats start losing precision
+        let input = r#"let main = 9007199254740993 + 2;"#;
+        assert_eq!(run(input).unwrap(), Value::Int(9007199254740995));
+        let result = run(r#"let main = 9223372036854775807 + 1;"#);
+        assert!(matches!(result, Err(RuntimeError::IntegerOverflow(_))));
+        let result = run(r#"let main = 4611686018427387904 * 2;"#);
+        assert!(matches!(result, Err(RuntimeError::IntegerOverflow(_))));
+        let result = run(r#"let main = 1 / 0;"#);
+        assert!(matches!(result, Err(RuntimeError::DivisionByZero)));
+    }
+
+    #[test]
+    fn test_float_arithmetic() {
+        assert_eq!(run(r#"let main = 7.0 / 2.0;"#).unwrap(), Value::Float(3.5));
+        assert_eq!(
+            run(r#"let main = -1.5 * 2.0;"#).unwrap(),
+            Value::Float(-3.0)
+        );
+        assert_eq!(
+            run(r#"let main = 1.0 / 0.0;"#).unwrap(),
+            Value::Float(f64::INFINITY)
+        );
+    }
+
+    #[test]
+    fn test_numeric_promotion() {
+        // mixing an int with a float gives a float
+        assert_eq!(run(r#"let main = 7 / 2.0;"#).unwrap(), Value::Float(3.5));
+        assert_eq!(run(r#"let main = 0.5 + 1;"#).unwrap(), Value::Float(1.5));
+        assert_eq!(run(r#"let main = 1 < 1.5;"#).unwrap(), Value::Bool(true));
+        assert_eq!(run(r#"let main = 2 == 2.0;"#).unwrap(), Value::Bool(true));
+        assert_eq!(run(r#"let main = 2 != 2.5;"#).unwrap(), Value::Bool(true));
+    }
+
+    #[test]
+    fn test_conversion_builtins() {
+        assert_eq!(
+            run(r#"let main = float(7) / 2;"#).unwrap(),
+            Value::Float(3.5)
+        );
+        assert_eq!(run(r#"let main = int(-3.9);"#).unwrap(), Value::Int(-3));
+        assert_eq!(
+            run(r#"let main = int(true) + int(2);"#).unwrap(),
+            Value::Int(3)
+        );
+        let result = run(r#"let main = int(1e19);"#);
+        assert!(matches!(result, Err(RuntimeError::IntegerOverflow(_))));
+        let result = run(r#"let main = float(false);"#);
+        assert!(matches!(result, Err(RuntimeError::TypeMismatch(_))));
+        let result = run(r#"let main = int(1, 2);"#);
+        assert!(matches!(result, Err(RuntimeError::ArityMismatch { .. })));
+    }
+
+    #[test]
+    fn test_strings() {
+        let result = run(r#"let main = "a\tb" + "c";"#);
+        assert_eq!(result.unwrap(), Value::String("a\tbc".into()));
+        let input = r#"let main = "abc" < "abd" && "b" > "abc" && "x" == "x" && "x" != "y";"#;
+        assert_eq!(run(input).unwrap(), Value::Bool(true));
+        let input = r#"let s = "héllo"; let main = s[1] + s[1..3] + s[3..] + s[..1];"#;
+        assert_eq!(run(input).unwrap(), Value::String("éélloh".into()));
+        let input = r#"let main = "abc"[2..1] == "" && "abc"[..] == "abc";"#;
+        assert_eq!(run(input).unwrap(), Value::Bool(true));
+        let result = run(r#"let main = "abc"[3];"#);
+        assert!(matches!(
+            result,
+            Err(RuntimeError::IndexOutOfBounds { index: 3, len: 3 })
+        ));
+        let result = run(r#"let main = "abc"[0..4];"#);
+        assert!(matches!(result, Err(RuntimeError::IndexOutOfBounds { .. })));
+        let result = run(r#"let main = "abc" - "c";"#);
+        assert!(matches!(result, Err(RuntimeError::TypeMismatch(_))));
+    }
+
+    #[test]
+    fn test_tuples() {
+        let input = r#"
+let divmod = fn(a, b) { (a / b, a - a / b * b) };
+let main = fn() {
+    let (q, r) = divmod(17, 5);
+    let t = (q, (r, "x"),);
+    t.0 * 10 + t.1.0
+}();"#;
+        assert_eq!(run(input).unwrap(), Value::Int(32));
+        let result = run(r#"let main = (1, (2.0, "a"));"#).unwrap();
+        assert_eq!(result.to_string(), r#"(1, (2.0, "a"))"#);
+        assert_eq!(run(r#"let main = (1,);"#).unwrap().to_string(), "(1,)");
+        let input = r#"let main = (1, (2, 3)) == (1.0, (2, 3)) && (1, 2) != (1, 2, 3);"#;
+        assert_eq!(run(input).unwrap(), Value::Bool(true));
+        let result = run(r#"let main = (1, 2).2;"#);
+        assert!(matches!(result, Err(RuntimeError::TypeMismatch(_))));
+    }
+
+    #[test]
+    fn test_destructuring() {
+        // nested patterns, under temporaries, captured by closures
+        let input = r#"
+let main = fn() {
+    let pair = ((1, 2), 3);
+    let sum = 100 + fn() {
+        let ((a, b), c) = pair;
+        let get = fn() { a * 100 + b * 10 + c };
+        get()
+    }();
+    sum
+}();"#;
+        assert_eq!(run(input).unwrap(), Value::Int(223));
+        let input = r#"let main = fn() { let (a, b) = (1, 2, 3); a }();"#;
+        assert!(matches!(run(input), Err(RuntimeError::TypeMismatch(_))));
+        let input = r#"let main = fn() { let (a, b) = 1; a }();"#;
+        assert!(matches!(run(input), Err(RuntimeError::TypeMismatch(_))));
+    }
+
+    #[test]
+    fn test_arrays() {
+        let input = r#"
+let main = fn() {
+    let a = [1, 2, [3]];
+    let b = a;
+    b[0] = 10;
+    a[2][0] = a[2][0] + 1;
+    push(a, 5);
+    (a, len(b), pop(b), a[1..3])
+}();"#;
+        assert_eq!(
+            run(input).unwrap().to_string(),
+            "([10, 2, [4]], 4, 5, [2, [4]])"
+        );
+        let input = r#"let main = [1, [2.0]] == [1.0, [2]] && [] != [1];"#;
+        assert_eq!(run(input).unwrap(), Value::Bool(true));
+        let result = run(r#"let main = fn() { let a = [1]; a[1] = 2; }();"#);
+        assert!(matches!(
+            result,
+            Err(RuntimeError::IndexOutOfBounds { index: 1, len: 1 })
+        ));
+        let result = run(r#"let main = [1][-1];"#);
+        assert!(matches!(
+            result,
+            Err(RuntimeError::IndexOutOfBounds { index: -1, len: 1 })
+        ));
+        let result = run(r#"let main = fn() { let t = (1,); t[0] = 2; }();"#);
+        assert!(matches!(result, Err(RuntimeError::TypeMismatch(_))));
+    }
+
+    #[test]
+    fn test_while() {
+        let input = r#"
+let main = fn() {
+    let i = 0;
+    let sum = 0;
+    while i < 10 {
+        i = i + 1;
+        if i / 2 * 2 == i { continue; };
+        if i > 7 { break; };
+        sum = sum + i;
+    };
+    sum
+}();"#;
+        assert_eq!(run(input).unwrap(), Value::Int(1 + 3 + 5 + 7));
+    }
+
+    #[test]
+    fn test_for() {
+        let input = r#"
+let main = fn() {
+    let out = [];
+    for i in 0..3 { push(out, i * i); };
+    for c in "hé🦀!" { push(out, c); };
+    for (a, b) in [(1, 2), (3, 4)] { push(out, a + b); };
+    out
+}();"#;
+        assert_eq!(
+            run(input).unwrap().to_string(),
+            r#"[0, 1, 4, "h", "é", "🦀", "!", 3, 7]"#
+        );
+        // break and continue drop the locals of the blocks they leave
+        let input = r#"
+let main = fn() {
+    let sum = 0;
+    for i in 0..100 {
+        let x = i * 2;
+        if x > 10 { let y = 1; break; };
+        if true { let z = 0; if i == 1 { continue; }; };
+        sum = sum + x;
+    };
+    let after = 1000;
+    sum + after
+}();"#;
+        assert_eq!(run(input).unwrap(), Value::Int(1000 + 4 + 6 + 8 + 10));
+        // long loops run in constant stack space
+        let input = r#"let main = fn() { let n = 0; for i in 0..100000 { n = n + 1; }; n }();"#;
+        assert_eq!(run(input).unwrap(), Value::Int(100000));
+        let result = run(r#"let main = fn() { for x in 3 { }; }();"#);
+        assert!(matches!(result, Err(RuntimeError::TypeMismatch(_))));
+    }
+
+    #[test]
+    fn test_loop_closures() {
+        // every iteration binds a fresh variable
+        let input = r#"
+let main = fn() {
+    let fs = [];
+    for i in 0..3 { push(fs, fn() { i }); };
+    (fs[0](), fs[2]())
+}();"#;
+        assert_eq!(run(input).unwrap().to_string(), "(0, 2)");
+    }
+
+    #[test]
+    fn test_break_outside_loop() {
+        for input in [
+            "let main = fn() { break; };",
+            "let main = fn() { while true { fn() { continue; }; }; };",
+        ] {
+            let program = parse_file(input).unwrap();
+            assert!(compile(program, input).is_err());
+        }
+    }
+
+    #[test]
+    fn test_structs() {
+        let input = r#"
+let main = fn() {
+    let p = Point { y: 2, x: 1 };
+    let q = p;
+    q.x = 10;
+    let line = Line { from: p, to: Point { x: 3, y: 4 } };
+    line.to.y = line.to.y + p.x;
+    (p, line.to.y, p == Point { x: 10, y: 2 }, Empty {})
+}();
+struct Point { x, y }
+struct Line { from, to }
+struct Empty {}"#;
+        assert_eq!(
+            run(input).unwrap().to_string(),
+            "(Point { x: 10, y: 2 }, 14, true, Empty {})"
+        );
+        // the same field at different offsets
+        let input = r#"
+struct A { x, y }
+struct B { y }
+let get_y = fn(s) { s.y };
+let main = get_y(A { x: 1, y: 2 }) + get_y(B { y: 3 });"#;
+        assert_eq!(run(input).unwrap(), Value::Int(5));
+        let result = run(r#"struct A { x } struct B { y } let main = B { y: 1 }.x;"#);
+        assert!(matches!(result, Err(RuntimeError::TypeMismatch(_))));
+        let result = run(r#"struct A { x } let main = (1, 2).x;"#);
+        assert!(matches!(result, Err(RuntimeError::TypeMismatch(_))));
+    }
+
+    #[test]
+    fn test_struct_errors() {
+        for input in [
+            "let main = P { x: 1 };",
+            "struct P { x } let main = P { };",
+            "struct P { x } let main = P { x: 1, y: 2 };",
+            "struct P { x } let main = P { x: 1, x: 2 };",
+            "struct P { x, x } let main = 1;",
+            "struct P { x } struct P { y } let main = 1;",
+            "struct P { x } let main = true.y;",
+        ] {
+            let program = parse_file(input).unwrap();
+            assert!(compile(program, input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_match() {
+        let input = r#"
+enum Shape { Circle(r), Rect(w, h), Empty }
+let area = fn(s) {
+    match s {
+        Shape::Circle(r) => 3 * r * r,
+        Shape::Rect(w, h) => { let a = w * h; a }
+        Shape::Empty => 0,
+    }
+};
+let main = (area(Shape::Circle(2)), area(Shape::Rect(2, 5)), area(Shape::Empty), Shape::Rect(1, 2));"#;
+        assert_eq!(
+            run(input).unwrap().to_string(),
+            "(12, 10, 0, Shape::Rect(1, 2))"
+        );
+        // nested patterns, literals, and the first arm that matches wins
+        let input = r#"
+enum Option { Some(x), None }
+let describe = fn(o) {
+    match o {
+        Option::Some(Option::Some(0)) => "zero",
+        Option::Some(Option::Some(n)) => format("{}", n),
+        Option::Some(Option::None) => "none inside",
+        Option::Some(_) => "other",
+        Option::None => "none",
+    }
+};
+let main = [
+    describe(Option::Some(Option::Some(0))),
+    describe(Option::Some(Option::Some(7))),
+    describe(Option::Some(Option::None)),
+    describe(Option::Some(1)),
+    describe(Option::None),
+];"#;
+        assert_eq!(
+            run(input).unwrap().to_string(),
+            r#"["zero", "7", "none inside", "other", "none"]"#
+        );
+        let input = r#"
+let main = fn() {
+    let out = [];
+    for i in 0..5 {
+        let name = match i { 0 => "zero", 1 => "one", n => { if n > 2 { break; }; "two" } };
+        push(out, name);
+    };
+    (out, match true { true => 1, false => 0 }, Option::None == Option::None)
+}();
+enum Option { Some(x), None }"#;
+        assert_eq!(
+            run(input).unwrap().to_string(),
+            r#"(["zero", "one", "two"], 1, true)"#
+        );
+        let input = r#"
+enum E { A, B }
+let main = match 1 { E::A => 1, E::B => 2 };"#;
+        assert!(matches!(run(input), Err(RuntimeError::NoMatch(_))));
+    }
+
+    #[test]
+    fn test_error_locations() {
+        let located = |input: &str| {
+            let program = parse_file(input).unwrap();
+            let error = compile(program, input).unwrap_err();
+            error.location.map(|(start, ..)| start)
+        };
+        let at = |line, col| Some(Position { line, col });
+        let input = "enum E { A, B }\nlet main = match E::A {\n    E::A => 1\n};";
+        assert_eq!(located(input), at(2, 12));
+        let input = "enum E { A(x) }\nlet main = match E::A(1) {\n    E::A(x, y) => 0\n};";
+        assert_eq!(located(input), at(2, 12));
+        assert_eq!(located("let main = fn() {\n    break;\n};"), at(2, 5));
+        assert_eq!(located("let main = 1 + P { x: 1 };"), at(1, 16));
+        assert_eq!(located("enum E { A, A }"), at(1, 1));
+    }
+
+    #[test]
+    fn test_match_errors() {
+        let compile_error = |input: &str| {
+            let program = parse_file(input).unwrap();
+            compile(program, input).unwrap_err().msg
+        };
+        let shape = "enum Shape { Circle(r), Rect(w, h) } enum O { Some(x), None }";
+        let error = compile_error(&format!(
+            "{shape} let main = match 1 {{ Shape::Circle(r) => r }};"
+        ));
+        assert_eq!(error, "The match does not cover `Shape::Rect(_, _)`");
+        let error = compile_error(&format!(
+            "{shape} let main = match 1 {{ O::Some(O::None) => 0, O::None => 1 }};"
+        ));
+        assert_eq!(error, "The match does not cover `O::Some(O::Some(_))`");
+        let error = compile_error("let main = match 1 { 1 => 0, 2 => 1 };");
+        assert_eq!(error, "The match does not cover `_`");
+        let error = compile_error("let main = match 1 { true => 0 };");
+        assert_eq!(error, "The match does not cover `false`");
+        for input in [
+            "let main = Shape::Circle(1);",
+            "enum E { A, A } let main = 1;",
+            "enum E { A(x) } let main = E::A;",
+            "enum E { A(x) } let main = E::B(1);",
+            "enum E { A(x) } let main = match E::A(1) { E::A(x, y) => 0 };",
+            "enum E { A } struct E { x } let main = 1;",
+        ] {
+            let program = parse_file(input).unwrap();
+            assert!(compile(program, input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_many_constants() {
+        // thousands of globals, each with a name and a value of its own
+        let mut input: String = (0..3000)
+            .map(|i| format!("let g{} = {}.5;\n", i, i))
+            .collect();
+        input.push_str("let main = g0 + g2999;");
+        assert_eq!(run(&input).unwrap(), Value::Float(3000.0));
+        // and thousands in one function
+        let body: String = (0..3000)
+            .map(|i| format!("total = total + {};\n", i))
+            .collect();
+        let input = format!(
+            "let f = fn() {{ let total = 0; {} total }}; let main = f();",
+            body
+        );
+        assert_eq!(run(&input).unwrap(), Value::Int(4498500));
+    }
+
+    #[test]
+    fn test_many_locals() {
+        // more elements than a byte counts, each a temporary on the stack
+        let elements = vec!["1"; 300].join(", ");
+        let input = format!("let main = len([{}]);", elements);
+        assert_eq!(run(&input).unwrap(), Value::Int(300));
+        // a block dropping exactly 256 locals
+        let lets: String = (0..255).map(|i| format!("let x{} = {};\n", i, i)).collect();
+        let input = format!(
+            "let f = fn(n) {{ {} if n > 0 {{ f(n - 1) }} else {{ x254 }} }}; let main = f(2);",
+            lets
+        );
+        assert_eq!(run(&input).unwrap(), Value::Int(254));
+        let input = format!(
+            "let main = if true {{ let y = 1; {} y + x254 }} else {{ 0 }};",
+            lets
+        );
+        assert_eq!(run(&input).unwrap(), Value::Int(255));
+    }
+
+    #[test]
+    fn test_too_many_constants() {
+        let input: String = (0..chunk::MAX_CONSTANTS / 2 + 1)
+            .map(|i| format!("let g{} = {};\n", i, i))
+            .collect();
+        let program = parse_file(&input).unwrap();
+        let error = compile(program, &input).unwrap_err();
+        assert!(error.msg.starts_with("Too many constants"), "{}", error);
+    }
+
+    #[test]
+    fn test_string_interning() {
+        let mut chunk = Chunk::default();
+        let a = chunk.add_string("shared").unwrap();
+        let mut child = chunk.child();
+        let b = child.add_string("shared").unwrap();
+        let (Value::String(a), Value::String(b)) =
+            (chunk.get_const(a as usize), child.get_const(b as usize))
+        else {
+            panic!("expected string constants");
+        };
+        assert!(Rc::ptr_eq(a, b));
+    }
+
+    #[test]
+    fn test_fib() {
+        let input = r#"
+let fib = fn (n) {
+    if n <= 1 { n } else { fib(n - 1) + fib(n - 2) }
+};
+let main = fib(10);"#;
+        assert_eq!(run(input).unwrap(), Value::Int(55));
+    }
+
+    #[test]
+    fn test_if_statement() {
+        let input = r#"
+let main = fn(c) {
+    let a = 1;
+    if c {
+        a = 2;
+    } else {
+        return 3;
+    };
+    a
+};
+let main = main(true) * 10 + main(false);"#;
+        assert_eq!(run(input).unwrap(), Value::Int(23));
+    }
+
+    #[test]
+    fn test_block_locals() {
+        // locals of a block are dropped at its end, under temporaries and all
+        let input = r#"
+let main = fn() {
+    let a = 1;
+    let b = 10 + if true { let a = 2; let c = a * 100; c + a } else { 0 };
+    a + b
+}();"#;
+        assert_eq!(run(input).unwrap(), Value::Int(213));
+        let input = r#"let main = 1 + if true { let x = 2; x } else { 0 };"#;
+        assert_eq!(run(input).unwrap(), Value::Int(3));
+        // captured block locals outlive the block
+        let input = r#"
+let main = fn() {
+    let get = if true { let x = 7; fn() { x } } else { fn() { 0 } };
+    let y = 8;
+    get()
+}();"#;
+        assert_eq!(run(input).unwrap(), Value::Int(7));
+    }
+
+    #[test]
+    fn test_set_non_local() {
+        let input = r#"let g = 1; let main = fn() { g = 2; };"#;
+        let program = parse_file(input).unwrap();
+        assert!(compile(program, input).is_err());
+    }
+
+    #[test]
+    fn test_call_errors() {
+        let result = run(r#"let id = fn(x) { x }; let main = id(1, 2);"#);
+        assert!(matches!(
+            result,
+            Err(RuntimeError::ArityMismatch {
+                expected: 1,
+                found: 2
+            })
+        ));
+        let result = run(r#"let a = 1; let main = a(2);"#);
+        assert!(matches!(result, Err(RuntimeError::NotCallable(_))));
+        let result = run(r#"let f = fn() { f() }; let main = f();"#);
+        assert!(matches!(result, Err(RuntimeError::StackOverflow)));
+    }
+
+    #[test]
+    fn test_traceback() {
+        let input = r#"let inner = fn(x) {
+    x + true
+};
+let outer = fn() { 1 + inner(2) };
+let main = outer();"#;
+        let program = parse_file(input).unwrap();
+        let chunk = compile(program, input).unwrap();
+        let traceback = VM::new().run(chunk).unwrap_err();
+        assert!(matches!(traceback.error, RuntimeError::TypeMismatch(_)));
+        let frames: Vec<_> = traceback
+            .frames
+            .iter()
+            .map(|frame| format!("{} {}", frame.function, frame.position))
+            .collect();
+        assert_eq!(frames, ["inner 2:5", "outer 4:24", "script 5:12"]);
+        assert_eq!(
+            traceback.render(Some("main.siren")).lines().nth(1),
+            Some("  at main.siren:2:5 in inner")
+        );
     }
 }
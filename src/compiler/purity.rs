@@ -0,0 +1,247 @@
+use std::collections::{HashMap, HashSet};
+
+use super::*;
+
+/// The builtins reading or writing outside the program.
+const IO_BUILTINS: [&str; 3] = ["print", "println", "read_line"];
+
+/// Checks that functions are pure from the outside: none assigns to a
+/// variable it does not declare, which would change the state of its caller
+/// or of the program. Returns the global functions performing I/O, through a
+/// builtin or another global doing so. Using such a value counts, and not
+/// only calling it: a function may be called through a variable.
+pub fn check_purity<'a>(
+    program: &Program<'a>,
+    source: &str,
+) -> Result<HashSet<&'a str>, CompileError> {
+    let globals: HashSet<_> = (program.0.iter())
+        .filter_map(|item| match &item.kind {
+            ItemKind::DefItem { ident, .. } => Some(ident.0),
+            _ => None,
+        })
+        .collect();
+    let mut uses = HashMap::new();
+    let mut functions = HashSet::new();
+    for item in &program.0 {
+        if let ItemKind::DefItem { ident, expr, .. } = &item.kind {
+            let mut walker = Walker::default();
+            walker
+                .expr(expr)
+                .map_err(|(msg, span)| CompileError::new(msg, source, span))?;
+            if matches!(expr.kind, ExprKind::Fn(_)) {
+                functions.insert(ident.0);
+            }
+            uses.insert(ident.0, walker.uses);
+        }
+    }
+    // a builtin redefined by the program is not one anymore
+    let mut io: HashSet<_> = (uses.iter())
+        .filter(|(_, used)| {
+            (used.iter()).any(|name| IO_BUILTINS.contains(name) && !globals.contains(name))
+        })
+        .map(|(name, _)| *name)
+        .collect();
+    loop {
+        let more: Vec<_> = (uses.iter())
+            .filter(|(name, used)| !io.contains(*name) && used.iter().any(|u| io.contains(u)))
+            .map(|(name, _)| *name)
+            .collect();
+        if more.is_empty() {
+            io.retain(|name| functions.contains(name));
+            return Ok(io);
+        }
+        io.extend(more);
+    }
+}
+
+/// Checks a REPL line as `check_purity` checks a program.
+pub fn check_line_purity(line: &Line, source: &str) -> Result<(), CompileError> {
+    let expr = match line {
+        Line::Item(Item {
+            kind: ItemKind::DefItem { expr, .. },
+            ..
+        }) => expr,
+        Line::Item(_) => return Ok(()),
+        Line::Expr(expr) => expr,
+    };
+    (Walker::default().expr(expr)).map_err(|(msg, span)| CompileError::new(msg, source, span))
+}
+
+/// Walks the code of a global, checking its assignments.
+#[derive(Default)]
+struct Walker<'a> {
+    /// The variables of the enclosing blocks, innermost last.
+    scopes: Vec<HashSet<&'a str>>,
+    /// How many scopes were open when each enclosing function started.
+    functions: Vec<usize>,
+    /// The names used which are not locals, so globals or builtins.
+    uses: HashSet<&'a str>,
+}
+
+impl<'a> Walker<'a> {
+    fn expr(&mut self, expr: &Expr<'a>) -> Result<(), (String, Span)> {
+        match &expr.kind {
+            ExprKind::Id(ident) => {
+                if !self.is_local(ident.0, 0) {
+                    self.uses.insert(ident.0);
+                }
+            }
+            ExprKind::Literal(_) | ExprKind::Path(..) => {}
+            ExprKind::BinOp(lhs, _, rhs) => {
+                self.expr(lhs)?;
+                self.expr(rhs)?;
+            }
+            ExprKind::Prefix(_, expr)
+            | ExprKind::TupleField { expr, .. }
+            | ExprKind::Field { expr, .. } => self.expr(expr)?,
+            ExprKind::Fn(function) => {
+                self.functions.push(self.scopes.len());
+                let params = function.params.iter().map(|param| param.ident.0);
+                self.scopes.push(params.collect());
+                let result = self.statements(&function.body);
+                self.scopes.pop();
+                self.functions.pop();
+                result?;
+            }
+            ExprKind::Call { func, args } => {
+                self.expr(func)?;
+                for arg in args {
+                    self.expr(arg)?;
+                }
+            }
+            ExprKind::If { cond, then, els } => {
+                self.expr(cond)?;
+                self.block(then)?;
+                if let Some(els) = els {
+                    self.block(els)?;
+                }
+            }
+            ExprKind::Index { expr, index } => {
+                self.expr(expr)?;
+                self.expr(index)?;
+            }
+            ExprKind::Slice { expr, start, end } => {
+                self.expr(expr)?;
+                for bound in [start, end].into_iter().flatten() {
+                    self.expr(bound)?;
+                }
+            }
+            ExprKind::Tuple(elements) | ExprKind::Array(elements) => {
+                for element in elements {
+                    self.expr(element)?;
+                }
+            }
+            ExprKind::Struct { fields, .. } => {
+                for (_, value) in fields {
+                    self.expr(value)?;
+                }
+            }
+            ExprKind::Match { expr, arms } => {
+                self.expr(expr)?;
+                for arm in arms {
+                    self.scopes.push(HashSet::new());
+                    self.declare(&arm.pattern);
+                    let result = self.block(&arm.body);
+                    self.scopes.pop();
+                    result?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn block(&mut self, block: &Block<'a>) -> Result<(), (String, Span)> {
+        self.scopes.push(HashSet::new());
+        let result = self
+            .statements(&block.body)
+            .and_then(|_| match &block.value {
+                Some(value) => self.expr(value),
+                None => Ok(()),
+            });
+        self.scopes.pop();
+        result
+    }
+
+    /// Walks statements in the innermost scope.
+    fn statements(&mut self, body: &[Statement<'a>]) -> Result<(), (String, Span)> {
+        for statement in body {
+            match &statement.kind {
+                StatementKind::Let(pattern, _, value) => {
+                    self.expr(value)?;
+                    self.declare(pattern);
+                }
+                StatementKind::Set(Ident(name, span), value) => {
+                    let start = self.functions.last().copied().unwrap_or(0);
+                    if !self.is_local(name, start) {
+                        let msg = format!(
+                            "Cannot assign to `{}`: it is not declared in this function",
+                            name
+                        );
+                        return Err((msg, *span));
+                    }
+                    self.expr(value)?;
+                }
+                // arrays and structs are shared, so what they hold may be set
+                // from any function which can reach them
+                StatementKind::SetIndex {
+                    target,
+                    index,
+                    value,
+                } => {
+                    self.expr(target)?;
+                    self.expr(index)?;
+                    self.expr(value)?;
+                }
+                StatementKind::SetField { target, value, .. } => {
+                    self.expr(target)?;
+                    self.expr(value)?;
+                }
+                StatementKind::Return(expr) | StatementKind::Expr(expr) => self.expr(expr)?,
+                StatementKind::While { cond, body } => {
+                    self.expr(cond)?;
+                    self.block(body)?;
+                }
+                StatementKind::For {
+                    pattern,
+                    iterable,
+                    body,
+                } => {
+                    self.expr(iterable)?;
+                    self.scopes.push(HashSet::new());
+                    self.declare(pattern);
+                    let result = self.block(body);
+                    self.scopes.pop();
+                    result?;
+                }
+                StatementKind::Break | StatementKind::Continue => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `name` is declared in the scopes from the `start`th on.
+    fn is_local(&self, name: &str, start: usize) -> bool {
+        self.scopes[start..]
+            .iter()
+            .any(|scope| scope.contains(name))
+    }
+
+    fn declare(&mut self, pattern: &Pattern<'a>) {
+        match pattern {
+            Pattern::Id(ident) => {
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.insert(ident.0);
+                }
+            }
+            Pattern::Tuple(patterns, _)
+            | Pattern::Variant {
+                fields: patterns, ..
+            } => {
+                for pattern in patterns {
+                    self.declare(pattern);
+                }
+            }
+            Pattern::Wildcard(_) | Pattern::Literal(..) => {}
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use super::*;
+
+/// Resolves the variables of nested functions at compile time.
+///
+/// Locals of the innermost function live in stack slots. Every `let` takes a
+/// fresh slot, so shadowing a name (even a parameter) never overwrites the old
+/// value: the initializer is compiled before the new name is declared, and
+/// later lookups find the most recent declaration.
+///
+/// Variables of enclosing functions are reached through upvalues, which are
+/// threaded through every function in between.
+///
+/// Temporaries that stay on the stack while a sub-expression is compiled take
+/// an unnamed slot, so locals declared inside that sub-expression (e.g. in the
+/// block of an `if`) still get the slot they will really occupy.
+pub struct Scope<'a> {
+    functions: Vec<FunctionScope<'a>>,
+}
+
+struct FunctionScope<'a> {
+    locals: Vec<Option<&'a str>>,
+    upvalues: Vec<Capture>,
+    // innermost last
+    loops: Vec<Loop>,
+}
+
+/// A loop being compiled, for its `break`s and `continue`s.
+struct Loop {
+    // where `continue` jumps back to
+    start: Pointer,
+    // the locals above this many are dropped when leaving an iteration
+    depth: usize,
+    // the jumps of the `break`s, patched once the loop is compiled
+    breaks: Vec<usize>,
+}
+
+impl<'a> Scope<'a> {
+    /// A scope for top-level code, which runs in the frame of the script itself.
+    pub fn new() -> Self {
+        let mut scope = Scope {
+            functions: Vec::new(),
+        };
+        scope.begin_function();
+        scope
+    }
+
+    pub fn begin_function(&mut self) {
+        self.functions.push(FunctionScope {
+            locals: Vec::new(),
+            upvalues: Vec::new(),
+            loops: Vec::new(),
+        });
+    }
+
+    /// Leaves the innermost function, returning what its closure has to capture.
+    pub fn end_function(&mut self) -> Vec<Capture> {
+        self.functions.pop().unwrap().upvalues
+    }
+
+    /// Declares a new local on top of the frame and returns its slot.
+    pub fn declare(&mut self, name: &'a str) -> Result<u16, String> {
+        self.push(Some(name))
+            .map_err(|_| format!("Too many local variables, when declaring `{}`", name))
+    }
+
+    /// Reserves the slot of a value left on the stack for a while.
+    pub fn push_temp(&mut self) -> Result<(), String> {
+        self.push(None)
+            .map(|_| ())
+            .map_err(|_| "Too many temporary values on the stack".to_string())
+    }
+
+    /// Forgets every local and temporary above `len`, returning how many there were.
+    pub fn truncate(&mut self, len: usize) -> usize {
+        let locals = &mut self.functions.last_mut().unwrap().locals;
+        let dropped = locals.len().saturating_sub(len);
+        locals.truncate(len);
+        dropped
+    }
+
+    fn push(&mut self, local: Option<&'a str>) -> Result<u16, ()> {
+        let function = self.functions.last_mut().unwrap();
+        // the number of slots fits too, for the instructions dropping them all
+        let len = u16::try_from(function.locals.len() + 1).map_err(|_| ())?;
+        function.locals.push(local);
+        Ok(len - 1)
+    }
+
+    pub fn resolve(&self, name: &str) -> Option<u16> {
+        self.functions
+            .last()
+            .and_then(|function| function.resolve(name))
+    }
+
+    pub fn resolve_upvalue(&mut self, name: &str) -> Result<Option<u8>, String> {
+        self.resolve_upvalue_at(self.functions.len(), name)
+    }
+
+    /// Enters a loop whose iterations start at `start`, with the current locals.
+    pub fn begin_loop(&mut self, start: Pointer) {
+        let depth = self.len();
+        let function = self.functions.last_mut().unwrap();
+        function.loops.push(Loop {
+            start,
+            depth,
+            breaks: Vec::new(),
+        });
+    }
+
+    /// Leaves the innermost loop, returning the jumps of its `break`s.
+    pub fn end_loop(&mut self) -> Vec<usize> {
+        let function = self.functions.last_mut().unwrap();
+        function.loops.pop().unwrap().breaks
+    }
+
+    /// The start and the depth of the innermost loop of the current function.
+    pub fn current_loop(&self) -> Option<(Pointer, usize)> {
+        let function = self.functions.last().unwrap();
+        function.loops.last().map(|l| (l.start, l.depth))
+    }
+
+    pub fn add_break(&mut self, jump: usize) {
+        let function = self.functions.last_mut().unwrap();
+        function.loops.last_mut().unwrap().breaks.push(jump);
+    }
+
+    pub fn len(&self) -> usize {
+        self.functions.last().unwrap().locals.len()
+    }
+
+    // `depth` counts the functions from the outermost one, so the function
+    // at `depth` is `self.functions[depth - 1]`.
+    fn resolve_upvalue_at(&mut self, depth: usize, name: &str) -> Result<Option<u8>, String> {
+        if depth < 2 {
+            return Ok(None);
+        }
+        if let Some(slot) = self.functions[depth - 2].resolve(name) {
+            let capture = Capture {
+                is_local: true,
+                index: slot,
+            };
+            return self.functions[depth - 1].add_upvalue(capture).map(Some);
+        }
+        if let Some(index) = self.resolve_upvalue_at(depth - 1, name)? {
+            let capture = Capture {
+                is_local: false,
+                index: index.into(),
+            };
+            return self.functions[depth - 1].add_upvalue(capture).map(Some);
+        }
+        Ok(None)
+    }
+}
+
+impl FunctionScope<'_> {
+    fn resolve(&self, name: &str) -> Option<u16> {
+        self.locals
+            .iter()
+            .rposition(|local| *local == Some(name))
+            .map(|slot| slot as u16)
+    }
+
+    fn add_upvalue(&mut self, capture: Capture) -> Result<u8, String> {
+        if let Some(ind) = self.upvalues.iter().position(|c| *c == capture) {
+            return Ok(ind as u8);
+        }
+        let ind = u8::try_from(self.upvalues.len())
+            .map_err(|_| "Too many captured variables in one function".to_string())?;
+        self.upvalues.push(capture);
+        Ok(ind)
+    }
+}
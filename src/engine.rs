@@ -0,0 +1,182 @@
+use std::rc::Rc;
+
+use super::*;
+
+/// A Siren interpreter for host programs. Sources loaded into it share their
+/// globals, which the host can read, set, and call, and their types.
+#[derive(Default)]
+pub struct Engine {
+    vm: VM,
+    types: Types,
+    env: TypeEnv,
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Engine::default()
+    }
+
+    /// Defines the items of a source as globals, without running `main`.
+    pub fn load(&mut self, source: &str) -> Result<(), SirenError> {
+        let program = parse_file(source).map_err(SirenError::Parse)?;
+        // checked against the sources loaded before, keeping its declarations once it runs
+        let mut env = self.env.clone();
+        check_module(&program, source, &mut env).map_err(SirenError::Type)?;
+        let code = compile_module(program, source, &self.types).map_err(SirenError::Compile)?;
+        self.vm.run(code).map_err(SirenError::Runtime)?;
+        self.env = env;
+        Ok(())
+    }
+
+    /// Runs one line as the REPL does: an item evaluates to unit, an expression to its value.
+    pub fn eval<R: FromValue>(&mut self, source: &str) -> Result<R, SirenError> {
+        let line = parse_line(source).map_err(SirenError::Parse)?;
+        let mut env = self.env.clone();
+        check_line(&line, source, &mut env).map_err(SirenError::Type)?;
+        let code = compile_line(line, source, &self.types).map_err(SirenError::Compile)?;
+        let value = self.vm.run(code).map_err(SirenError::Runtime)?;
+        self.env = env;
+        R::from_value(value).map_err(host_error)
+    }
+
+    /// Calls the global function `name`, e.g. `engine.call::<i64>("add", (1, 2))`.
+    pub fn call<R: FromValue>(&mut self, name: &str, args: impl IntoArgs) -> Result<R, SirenError> {
+        let callee = self.global(name)?.clone();
+        let value = self
+            .vm
+            .call(callee, args.into_args())
+            .map_err(SirenError::Runtime)?;
+        R::from_value(value).map_err(host_error)
+    }
+
+    pub fn get_global<R: FromValue>(&self, name: &str) -> Result<R, SirenError> {
+        R::from_value(self.global(name)?.clone()).map_err(host_error)
+    }
+
+    pub fn set_global(&mut self, name: &str, value: impl IntoValue) {
+        self.vm.set_global(name, value.into_value());
+        self.env.forget(name);
+    }
+
+    /// Binds a Rust closure to the global `name`, e.g.
+    /// `engine.register("sqrt", |x: f64| x.sqrt())`. Arguments are converted
+    /// with `FromValue`, and a mismatch is reported as `RuntimeError::BadArgument`.
+    pub fn register<Args>(&mut self, name: &str, func: impl IntoNative<Args>) {
+        let native = Value::Native(Rc::new(func.into_native(name)));
+        self.vm.set_global(name, native);
+        self.env.forget(name);
+    }
+
+    fn global(&self, name: &str) -> Result<&Value, SirenError> {
+        self.vm
+            .globals()
+            .get(name)
+            .ok_or_else(|| host_error(RuntimeError::UndefinedVariable(name.to_string())))
+    }
+}
+
+// errors raised outside of any Siren frame
+fn host_error(error: RuntimeError) -> SirenError {
+    SirenError::Runtime(Traceback {
+        error,
+        frames: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_engine() {
+        let mut engine = Engine::new();
+        engine
+            .load("let add = fn(a, b) { a + b }; let scale = 10;")
+            .unwrap();
+        engine
+            .load("let scaled = fn(x) { add(x, 0) * scale };")
+            .unwrap();
+        assert_eq!(engine.call::<i64>("add", (1, 2)).unwrap(), 3);
+        assert_eq!(engine.call::<f64>("add", (1, 0.5)).unwrap(), 1.5);
+        engine.set_global("scale", 3);
+        assert_eq!(engine.call::<i64>("scaled", (4,)).unwrap(), 12);
+        assert_eq!(engine.get_global::<i64>("scale").unwrap(), 3);
+        assert!(engine.eval::<bool>("scaled(1) == 3").unwrap());
+        assert_eq!(engine.call::<i64>("int", (2.5,)).unwrap(), 2);
+        engine.load("let rev = fn(a) { [a[1], a[0]] };").unwrap();
+        let reversed = engine.call::<Vec<i64>>("rev", (vec![1, 2],)).unwrap();
+        assert_eq!(reversed, [2, 1]);
+    }
+
+    #[test]
+    fn test_register() {
+        let mut engine = Engine::new();
+        engine.register("sqrt", |x: f64| x.sqrt());
+        engine.register("greet", |name: String, times: i64| {
+            name.repeat(times as usize)
+        });
+        engine.register("checked_div", |a: i64, b: i64| match b {
+            0 => Err(RuntimeError::Native("cannot divide by zero".to_string())),
+            b => Ok(a / b),
+        });
+        assert_eq!(engine.eval::<f64>("sqrt(16)").unwrap(), 4.0);
+        assert_eq!(engine.call::<String>("greet", ("ab", 2)).unwrap(), "abab");
+        assert_eq!(engine.eval::<i64>("checked_div(7, 2)").unwrap(), 3);
+        let error = engine.eval::<i64>("checked_div(1, 0)").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "error: cannot divide by zero\n  at <input>:1:1 in script"
+        );
+        let error = engine.eval::<f64>("sqrt(true)").unwrap_err();
+        assert!(matches!(
+            error,
+            SirenError::Runtime(Traceback {
+                error: RuntimeError::BadArgument { index: 1, .. },
+                ..
+            })
+        ));
+        let error = engine.eval::<f64>("sqrt(1, 2)").unwrap_err();
+        assert!(matches!(
+            error,
+            SirenError::Runtime(Traceback {
+                error: RuntimeError::ArityMismatch {
+                    expected: 1,
+                    found: 2
+                },
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_engine_errors() {
+        let mut engine = Engine::new();
+        engine.load("let id = fn(x) { x };").unwrap();
+        let error = engine.call::<bool>("id", (1,)).unwrap_err();
+        assert!(matches!(
+            error,
+            SirenError::Runtime(Traceback {
+                error: RuntimeError::TypeMismatch(_),
+                ..
+            })
+        ));
+        let error = engine.call::<i64>("missing", ()).unwrap_err();
+        assert_eq!(error.to_string(), "error: Undefined variable: missing");
+        let error = engine.call::<i64>("id", (1, 2)).unwrap_err();
+        assert!(std::error::Error::source(&error).is_some());
+        assert!(matches!(
+            engine.load("let a = ;"),
+            Err(SirenError::Parse(_))
+        ));
+        // a source is checked against the ones loaded before
+        engine.load("let twice = fn(n: int) { n * 2 };").unwrap();
+        assert!(matches!(
+            engine.load("let main = twice(\"a\");"),
+            Err(SirenError::Type(_))
+        ));
+        assert!(matches!(
+            engine.eval::<i64>("let b: bool = twice(1)"),
+            Err(SirenError::Type(_))
+        ));
+    }
+}
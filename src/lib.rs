@@ -1,45 +1,276 @@
+mod checker;
 mod compiler;
+mod engine;
+mod optimizer;
 mod parser;
 mod stack_vm;
 
+use std::{cell::RefCell, fmt::Display, rc::Rc};
+
+use checker::*;
 use compiler::*;
+use optimizer::optimize;
 use parser::*;
 use stack_vm::*;
 
+#[derive(Debug)]
 pub enum SirenError {
     Parse(ParserError),
-    Compile(String),
-    Runtime(RuntimeError),
+    Type(TypeError),
+    Compile(CompileError),
+    Runtime(Traceback),
+}
+
+impl Display for SirenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SirenError::Parse(error) => write!(f, "{}", error),
+            SirenError::Type(error) => write!(f, "{}", error),
+            SirenError::Compile(error) => write!(f, "{}", error),
+            SirenError::Runtime(traceback) => write!(f, "{}", traceback),
+        }
+    }
+}
+
+impl std::error::Error for SirenError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SirenError::Parse(error) => Some(error),
+            SirenError::Type(error) => Some(error),
+            SirenError::Compile(error) => Some(error),
+            SirenError::Runtime(traceback) => Some(&traceback.error),
+        }
+    }
 }
+
+pub use checker::TypeError;
+pub use compiler::CompileError;
+pub use engine::Engine;
 pub use parser::parse_file;
 pub use parser::pretty_print_program;
+pub use parser::{ErrorKind, ParserError, Position};
+pub use stack_vm::{
+    FromValue, IntoArgs, IntoNative, IntoValue, NativeResult, RuntimeError, TraceFrame, Traceback,
+    Value,
+};
+
+/// What `run_file` prints while running a program. Nothing, by default.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RunOptions {
+    /// Print every executed instruction with the stack after it.
+    pub trace: bool,
+    pub dump_ast: bool,
+    pub dump_bytecode: bool,
+    /// How much to optimize the program before compiling it, from 0 for not at all to 2.
+    pub opt_level: u8,
+}
 
-pub fn run_file(input: &str) -> Result<(), SirenError> {
+/// Runs a program, returning the value of its `main`.
+pub fn run_file(input: &str, options: RunOptions) -> Result<Value, SirenError> {
+    let mut program = parse_file(input).map_err(SirenError::Parse)?;
+    if options.dump_ast {
+        println!("== AST ==");
+        println!("{}", program);
+    }
+    check_program(&program, input).map_err(SirenError::Type)?;
+    if options.opt_level > 0 {
+        optimize(&mut program, options.opt_level);
+        if options.dump_ast {
+            println!("== Optimized AST ==");
+            println!("{}", program);
+        }
+    }
+    let code = compile(program, input).map_err(SirenError::Compile)?;
+    if options.dump_bytecode {
+        disassemble_chunk(&code, "Compiled Code");
+    }
+    let mut vm = VM::new();
+    vm.set_trace(options.trace);
+    vm.run(code).map_err(SirenError::Runtime)
+}
+
+/// Checks a program without running it, returning the inferred type of each
+/// global, in order, as its name and its type. The type of a function
+/// performing I/O ends with `with io`.
+pub fn check_file(input: &str) -> Result<Vec<(String, String)>, SirenError> {
     let program = parse_file(input).map_err(SirenError::Parse)?;
-    println!("== AST ==");
-    println!("{}", program);
-    let code = compile(program).map_err(SirenError::Compile)?;
-    disassemble_chunk(&code, "Compiled Code");
-    println!("== Stack Machine Execution ==");
-    let result = VM::new(&code).run().map_err(SirenError::Runtime)?;
-    println!("Output:");
-    for value in result {
-        print!("[{}]", value);
-    }
-    Ok(())
-}
-
-// pub fn run_line(input: &str) -> Result<(), String> {
-//     let item = parse_line(input)?.unwrap();
-//     let code = compile_item(item).unwrap();
-//     let mut vm = VM::new(&code);
-//     match vm.run() {
-//         Ok(v) => {
-//             println!("Output: {}", v);
-//         }
-//         Err(e) => {
-//             return Err(format!("RuntimeError: {}", e));
-//         }
-//     }
-//     Ok(())
-// }
+    let types = check_program(&program, input).map_err(SirenError::Type)?;
+    let io = check_purity(&program, input).map_err(SirenError::Compile)?;
+    let types = (types.into_iter())
+        .map(|(name, ty)| match io.contains(name.as_str()) {
+            true => (name, format!("{} with io", ty)),
+            false => (name, ty),
+        })
+        .collect();
+    Ok(types)
+}
+
+/// Keeps one VM alive across REPL lines, so that the globals and the types
+/// defined by a line are visible to the following ones.
+#[derive(Default)]
+pub struct Repl {
+    vm: VM,
+    types: Types,
+    env: TypeEnv,
+}
+
+impl Repl {
+    pub fn new() -> Self {
+        Repl::default()
+    }
+
+    /// Runs a line, returning the value of an expression, or `None` for an item.
+    pub fn run_line(&mut self, input: &str) -> Result<Option<String>, SirenError> {
+        let line = parse_line(input).map_err(SirenError::Parse)?;
+        let is_item = matches!(line, Line::Item(_));
+        // the declarations of a line are only kept when it runs
+        let mut env = self.env.clone();
+        check_line(&line, input, &mut env).map_err(SirenError::Type)?;
+        let code = compile_line(line, input, &self.types).map_err(SirenError::Compile)?;
+        let result = self.vm.run(code).map_err(SirenError::Runtime)?;
+        self.env = env;
+        Ok((!is_item).then(|| result.to_string()))
+    }
+
+    pub fn print_ast(&self, input: &str) -> Result<(), SirenError> {
+        let line = parse_line(input).map_err(SirenError::Parse)?;
+        println!("{}", line);
+        Ok(())
+    }
+
+    pub fn print_bytecode(&self, input: &str) -> Result<(), SirenError> {
+        let line = parse_line(input).map_err(SirenError::Parse)?;
+        check_line(&line, input, &mut self.env.clone()).map_err(SirenError::Type)?;
+        // a declaration is only kept when the line runs
+        let types = Rc::new(RefCell::new(self.types.borrow().clone()));
+        let code = compile_line(line, input, &types).map_err(SirenError::Compile)?;
+        disassemble_chunk(&code, "Compiled Code");
+        Ok(())
+    }
+
+    pub fn print_globals(&self) {
+        for (name, value) in self.defined_globals() {
+            println!("{} = {}", name, value);
+        }
+    }
+
+    /// The globals sorted by name, leaving out the builtins still bound to
+    /// their own name.
+    fn defined_globals(&self) -> Vec<(&Rc<str>, &Value)> {
+        let mut globals: Vec<_> = (self.vm.globals().iter())
+            .filter(
+                |(name, value)| !matches!(value, Value::Native(native) if native.name == ***name),
+            )
+            .collect();
+        globals.sort_by_key(|(name, _)| *name);
+        globals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_file_returns_main() {
+        let input = "let sq = fn(x) { x * x }; let main = sq(7);";
+        let result = run_file(input, RunOptions::default());
+        assert_eq!(result.ok(), Some(Value::Int(49)));
+        // `main` is a value, a function is not called
+        let result = run_file("let main = fn() { 1 };", RunOptions::default());
+        assert_eq!(result.unwrap().to_string(), "<fn main>");
+        let input = include_str!("../examples/branch.siren");
+        let result = run_file(input, RunOptions::default());
+        assert_eq!(result.ok(), Some(Value::Unit));
+    }
+
+    #[test]
+    fn test_run_file_checks_types() {
+        let input = "let sq = fn(x: int) -> int { x * x }; let main = sq(\"7\");";
+        let result = run_file(input, RunOptions::default());
+        assert!(matches!(result, Err(SirenError::Type(_))));
+    }
+
+    #[test]
+    fn test_run_file_optimized() {
+        let input = r#"
+let unused = fn() { 1 / 0 };
+let fact = fn(n) { if n < 2 { return 1; 0; }; n * fact(n - 1) };
+let main = fact(10) - 2 * 3 + 0;"#;
+        for opt_level in 0..=2 {
+            let options = RunOptions {
+                opt_level,
+                ..RunOptions::default()
+            };
+            assert_eq!(run_file(input, options).ok(), Some(Value::Int(3628794)));
+        }
+    }
+
+    #[test]
+    fn test_check_file_infers_types() {
+        let input = "let sq = fn(x) { x * x }; let main = sq(7);";
+        let types = check_file(input).unwrap();
+        assert_eq!(
+            types,
+            [
+                ("sq".to_string(), "fn<T: num>(T) -> T".to_string()),
+                ("main".to_string(), "int".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_repl_keeps_globals() {
+        let mut repl = Repl::new();
+        assert_eq!(repl.run_line("let a = 40").ok(), Some(None));
+        assert_eq!(repl.run_line("let inc = fn(x) { x + 1 };").ok(), Some(None));
+        assert_eq!(
+            repl.run_line("inc(a) + 1").ok(),
+            Some(Some("42".to_string()))
+        );
+        // a failing line leaves the globals in place
+        assert!(repl.run_line("missing").is_err());
+        assert!(repl.run_line("let b = 1 / 0").is_err());
+        assert_eq!(repl.run_line("a").ok(), Some(Some("40".to_string())));
+    }
+
+    #[test]
+    fn test_repl_checks_types() {
+        let mut repl = Repl::new();
+        let result = repl.run_line("let a: int = \"x\"");
+        assert!(matches!(result, Err(SirenError::Type(_))));
+        assert_eq!(
+            repl.run_line("let inc = fn(x: int) { x + 1 }").ok(),
+            Some(None)
+        );
+        assert!(matches!(
+            repl.run_line("inc(\"a\")"),
+            Err(SirenError::Type(_))
+        ));
+        // the types of a failing line are not kept
+        assert!(repl.run_line("let b = 1 / 0").is_err());
+        assert!(matches!(repl.run_line("b"), Err(SirenError::Runtime(_))));
+    }
+
+    #[test]
+    fn test_repl_lists_defined_globals() {
+        let mut repl = Repl::new();
+        assert!(repl.defined_globals().is_empty());
+        repl.run_line("let b = 2").unwrap();
+        repl.run_line("let a = print").unwrap();
+        repl.run_line("let sqrt = fn(x) { x }").unwrap();
+        let names: Vec<_> = (repl.defined_globals().iter())
+            .map(|(name, _)| name.to_string())
+            .collect();
+        assert_eq!(names, ["a", "b", "sqrt"]);
+    }
+
+    #[test]
+    fn test_repl_keeps_structs() {
+        let mut repl = Repl::new();
+        assert_eq!(repl.run_line("struct P { x, y }").ok(), Some(None));
+        assert_eq!(repl.run_line("let p = P { x: 1, y: 2 }").ok(), Some(None));
+        assert_eq!(repl.run_line("p.y").ok(), Some(Some("2".to_string())));
+        assert!(repl.run_line("struct P { z }").is_err());
+    }
+}
@@ -1,7 +1,13 @@
 use clap::{Parser, Subcommand};
 use colored::Colorize;
-use siren_language::{parse_file, pretty_print_program, run_file, SirenError};
-use std::{fs, path::PathBuf};
+use siren_language::{
+    check_file, parse_file, pretty_print_program, run_file, Repl, RunOptions, SirenError, Value,
+};
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
 
 #[derive(Parser)]
 struct Cli {
@@ -12,6 +18,19 @@ struct Cli {
 #[derive(Subcommand)]
 enum Command {
     Run {
+        #[arg(long, help = "Print every executed instruction and the stack")]
+        trace: bool,
+        #[arg(long, help = "Print the AST before running")]
+        dump_ast: bool,
+        #[arg(long, help = "Print the compiled code before running")]
+        dump_bytecode: bool,
+        #[arg(
+            long,
+            default_value_t = 0,
+            value_parser = clap::value_parser!(u8).range(0..=2),
+            help = "Optimize before compiling: 1 folds literals and removes unreachable code, 2 also drops unused globals"
+        )]
+        opt_level: u8,
         #[arg(
             value_name = "source file",
             help = "Path to the source file to interpret"
@@ -27,28 +46,48 @@ enum Command {
         )]
         file: PathBuf,
     },
+    /// Type-check a file without running it
+    Check {
+        #[arg(long, help = "Print the inferred type of each global")]
+        types: bool,
+        #[arg(value_name = "source file", help = "Path to the source file to check")]
+        file: PathBuf,
+    },
+    /// Start an interactive session
+    Repl,
 }
 
 fn main() {
     let cli = Cli::parse();
     match cli.command {
-        Command::Run { file } => file_interpreter(file),
+        Command::Run {
+            trace,
+            dump_ast,
+            dump_bytecode,
+            opt_level,
+            file,
+        } => {
+            let options = RunOptions {
+                trace,
+                dump_ast,
+                dump_bytecode,
+                opt_level,
+            };
+            file_interpreter(file, options)
+        }
         Command::Parse { pretty, file } => print_ast(pretty, file),
+        Command::Check { types, file } => check(types, file),
+        Command::Repl => repl(),
     }
 }
 
-fn file_interpreter(path: PathBuf) {
+fn file_interpreter(path: PathBuf, options: RunOptions) {
     match fs::read_to_string(path.clone()) {
-        Ok(content) => {
-            if let Err(msg) = run_file(&content) {
-                let e = match msg {
-                    SirenError::Parse(msg) => format!("Parse error:\n{}", msg),
-                    SirenError::Compile(msg) => format!("Compilation error:\n{}", msg),
-                    SirenError::Runtime(msg) => format!("Runtime error:\n{}", msg),
-                };
-                println!("{}", e.red());
-            }
-        }
+        Ok(content) => match run_file(&content, options) {
+            Ok(Value::Unit) => {}
+            Ok(value) => println!("{}", value),
+            Err(msg) => report(msg, Some(&path)),
+        },
         Err(e) => println!(
             "{}\n{}",
             e.to_string().red(),
@@ -62,7 +101,26 @@ fn print_ast(pretty: bool, file: PathBuf) {
         Ok(content) => match (pretty, parse_file(&content)) {
             (false, Ok(p)) => println!("{}", p),
             (true, Ok(p)) => pretty_print_program(&p, 0),
-            (_, Err(msg)) => println!("Parse error:\n{}", msg),
+            (_, Err(msg)) => report(SirenError::Parse(msg), Some(&file)),
+        },
+        Err(e) => println!(
+            "{}\n{}",
+            e.to_string().red(),
+            format!("Path: {:?}", file).red()
+        ),
+    }
+}
+
+fn check(print_types: bool, file: PathBuf) {
+    match fs::read_to_string(file.clone()) {
+        Ok(content) => match check_file(&content) {
+            Ok(types) if print_types => {
+                for (name, ty) in types {
+                    println!("{}: {}", name, ty);
+                }
+            }
+            Ok(_) => {}
+            Err(msg) => report(msg, Some(&file)),
         },
         Err(e) => println!(
             "{}\n{}",
@@ -71,3 +129,101 @@ fn print_ast(pretty: bool, file: PathBuf) {
         ),
     }
 }
+
+const REPL_HELP: &str = "\
+:ast <line>       show the AST of a line without running it
+:bytecode <line>  show the compiled code of a line without running it
+:globals          list the global variables
+:help             show this message
+:quit             leave the REPL";
+
+fn repl() {
+    let mut repl = Repl::new();
+    while let Some(input) = read_input() {
+        let input = input.trim();
+        let (command, rest) = input.split_once(' ').unwrap_or((input, ""));
+        let result = match command {
+            "" => Ok(()),
+            ":quit" | ":q" => break,
+            ":help" => {
+                println!("{}", REPL_HELP);
+                Ok(())
+            }
+            ":globals" => {
+                repl.print_globals();
+                Ok(())
+            }
+            ":ast" => repl.print_ast(rest),
+            ":bytecode" => repl.print_bytecode(rest),
+            _ if command.starts_with(':') => {
+                println!(
+                    "{}",
+                    format!("Unknown command {}, try :help", command).red()
+                );
+                Ok(())
+            }
+            _ => repl.run_line(input).map(|value| {
+                if let Some(value) = value {
+                    println!("{}", value);
+                }
+            }),
+        };
+        if let Err(e) = result {
+            report(e, None);
+        }
+    }
+}
+
+/// Reads one input, which spans several lines while its braces are unbalanced.
+/// Returns `None` at the end of the input.
+fn read_input() -> Option<String> {
+    let mut input = String::new();
+    loop {
+        print!("{}", if input.is_empty() { "> " } else { ". " });
+        io::stdout().flush().ok()?;
+        match io::stdin().read_line(&mut input) {
+            Ok(0) if input.is_empty() => return None,
+            Ok(0) => return Some(input),
+            Ok(_) if open_brackets(&input) > 0 => continue,
+            Ok(_) => return Some(input),
+            Err(_) => return None,
+        }
+    }
+}
+
+/// Counts the brackets left open in `input`, ignoring those in string literals.
+fn open_brackets(input: &str) -> isize {
+    let mut depth = 0;
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' | '(' | '[' => depth += 1,
+            '}' | ')' | ']' => depth -= 1,
+            '"' => {
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    depth
+}
+
+fn report(error: SirenError, path: Option<&Path>) {
+    let path = path.map(|path| path.display().to_string());
+    let path = path.as_deref();
+    let e = match error {
+        SirenError::Parse(msg) => format!("Parse error:\n{}", msg.render(path)),
+        SirenError::Type(error) => format!("Type error:\n{}", error.render(path)),
+        SirenError::Compile(error) => format!("Compilation error:\n{}", error.render(path)),
+        SirenError::Runtime(traceback) => format!("Runtime error:\n{}", traceback.render(path)),
+    };
+    println!("{}", e.red());
+}
@@ -0,0 +1,350 @@
+use std::{borrow::Cow, collections::HashSet, mem};
+
+use super::*;
+
+/// Rewrites a checked program into one doing the same with less work. From
+/// level 1 on, arithmetic on literals is folded and the statements following
+/// a `return`, `break` or `continue` are removed; from level 2 on, the
+/// globals `main` does not use are dropped too. Level 0 leaves it unchanged.
+pub fn optimize(program: &mut Program, level: u8) {
+    if level == 0 {
+        return;
+    }
+    for item in &mut program.0 {
+        if let ItemKind::DefItem { expr, .. } = &mut item.kind {
+            fold(expr);
+        }
+    }
+    if level >= 2 {
+        drop_unused(program);
+    }
+}
+
+/// Folds the literal operations of `expr` and of the code it contains.
+fn fold(expr: &mut Expr) {
+    match &mut expr.kind {
+        ExprKind::Id(_) | ExprKind::Literal(_) | ExprKind::Path(..) => {}
+        ExprKind::BinOp(lhs, _, rhs) => {
+            fold(lhs);
+            fold(rhs);
+        }
+        ExprKind::Prefix(_, expr)
+        | ExprKind::TupleField { expr, .. }
+        | ExprKind::Field { expr, .. } => fold(expr),
+        ExprKind::Fn(function) => statements(&mut function.body),
+        ExprKind::Call { func, args } => {
+            fold(func);
+            args.iter_mut().for_each(fold);
+        }
+        ExprKind::If { cond, then, els } => {
+            fold(cond);
+            block(then);
+            if let Some(els) = els {
+                block(els);
+            }
+        }
+        ExprKind::Index { expr, index } => {
+            fold(expr);
+            fold(index);
+        }
+        ExprKind::Slice { expr, start, end } => {
+            fold(expr);
+            for bound in [start, end].into_iter().flatten() {
+                fold(bound);
+            }
+        }
+        ExprKind::Tuple(elements) | ExprKind::Array(elements) => {
+            elements.iter_mut().for_each(fold);
+        }
+        ExprKind::Struct { fields, .. } => {
+            fields.iter_mut().for_each(|(_, value)| fold(value));
+        }
+        ExprKind::Match { expr, arms } => {
+            fold(expr);
+            arms.iter_mut().for_each(|arm| block(&mut arm.body));
+        }
+    }
+    let literal = match &expr.kind {
+        ExprKind::BinOp(lhs, op @ (Infix::And | Infix::Or), _) => {
+            let ExprKind::Literal(Literal::Boolean(b)) = lhs.kind else {
+                return;
+            };
+            // the rhs is the result when the lhs does not decide it
+            if matches!((op, b), (Infix::And, true) | (Infix::Or, false)) {
+                let kind = mem::replace(&mut expr.kind, ExprKind::Tuple(Vec::new()));
+                if let ExprKind::BinOp(_, _, rhs) = kind {
+                    *expr = *rhs;
+                }
+                return;
+            }
+            Some(Literal::Boolean(b))
+        }
+        ExprKind::BinOp(lhs, op, rhs) => match (&lhs.kind, &rhs.kind) {
+            (ExprKind::Literal(a), ExprKind::Literal(b)) => binary(a, op, b),
+            _ => None,
+        },
+        ExprKind::Prefix(op, rhs) => match &rhs.kind {
+            ExprKind::Literal(a) => prefix(op, a),
+            _ => None,
+        },
+        _ => None,
+    };
+    if let Some(literal) = literal {
+        expr.kind = ExprKind::Literal(literal);
+    }
+}
+
+/// The value of a literal operation, as the VM computes it. `None` when it
+/// fails at runtime, which is left to happen there, or cannot be folded.
+fn binary<'a>(a: &Literal<'a>, op: &Infix, b: &Literal<'a>) -> Option<Literal<'a>> {
+    use Literal::*;
+    let literal = match (a, op, b) {
+        (_, Infix::Eq, _) => Boolean(equal(a, b)?),
+        (_, Infix::Ne, _) => Boolean(!equal(a, b)?),
+        (Int(a), _, Int(b)) => match op {
+            Infix::Add => Int(a.checked_add(*b)?),
+            Infix::Sub => Int(a.checked_sub(*b)?),
+            Infix::Mul => Int(a.checked_mul(*b)?),
+            Infix::Div if *b == 0 => return None,
+            Infix::Div => Int(a.checked_div(*b)?),
+            _ => Boolean(compare(op, a, b)?),
+        },
+        (Int(_) | Float(_), _, Int(_) | Float(_)) => {
+            let (a, b) = (number(a)?, number(b)?);
+            match op {
+                Infix::Add => Float(a + b),
+                Infix::Sub => Float(a - b),
+                Infix::Mul => Float(a * b),
+                Infix::Div => Float(a / b),
+                _ => Boolean(compare(op, &a, &b)?),
+            }
+        }
+        (String(a), Infix::Add, String(b)) => String(Cow::Owned([a.as_ref(), b].concat())),
+        (String(a), _, String(b)) => Boolean(compare(op, a, b)?),
+        _ => return None,
+    };
+    Some(literal)
+}
+
+fn prefix<'a>(op: &Prefix, a: &Literal<'a>) -> Option<Literal<'a>> {
+    match (op, a) {
+        (Prefix::Neg, Literal::Int(n)) => Some(Literal::Int(n.checked_neg()?)),
+        (Prefix::Neg, Literal::Float(n)) => Some(Literal::Float(-n)),
+        (Prefix::Not, Literal::Boolean(b)) => Some(Literal::Boolean(!b)),
+        _ => None,
+    }
+}
+
+/// Whether two literals are equal, an int being equal to the same float.
+fn equal(a: &Literal, b: &Literal) -> Option<bool> {
+    match (a, b) {
+        (Literal::Int(a), Literal::Int(b)) => Some(a == b),
+        (Literal::Boolean(a), Literal::Boolean(b)) => Some(a == b),
+        (Literal::String(a), Literal::String(b)) => Some(a == b),
+        (Literal::Int(_) | Literal::Float(_), Literal::Int(_) | Literal::Float(_)) => {
+            Some(number(a)? == number(b)?)
+        }
+        _ => Some(false),
+    }
+}
+
+fn number(literal: &Literal) -> Option<f64> {
+    match literal {
+        Literal::Int(n) => Some(*n as f64),
+        Literal::Float(n) => Some(*n),
+        _ => None,
+    }
+}
+
+fn compare<T: PartialOrd + ?Sized>(op: &Infix, a: &T, b: &T) -> Option<bool> {
+    match op {
+        Infix::Lt => Some(a < b),
+        Infix::Le => Some(a <= b),
+        Infix::Gt => Some(a > b),
+        Infix::Ge => Some(a >= b),
+        _ => None,
+    }
+}
+
+fn block(block: &mut Block) {
+    statements(&mut block.body);
+    if diverges(&block.body) {
+        block.value = None;
+    } else if let Some(value) = &mut block.value {
+        fold(value);
+    }
+}
+
+/// Folds the statements and removes the ones which cannot be reached.
+fn statements(body: &mut Vec<Statement>) {
+    if let Some(end) = body.iter().position(|statement| {
+        matches!(
+            statement.kind,
+            StatementKind::Return(_) | StatementKind::Break | StatementKind::Continue
+        )
+    }) {
+        body.truncate(end + 1);
+    }
+    for statement in body {
+        match &mut statement.kind {
+            StatementKind::Let(_, _, expr)
+            | StatementKind::Return(expr)
+            | StatementKind::Expr(expr)
+            | StatementKind::Set(_, expr) => fold(expr),
+            StatementKind::SetIndex {
+                target,
+                index,
+                value,
+            } => {
+                fold(target);
+                fold(index);
+                fold(value);
+            }
+            StatementKind::SetField { target, value, .. } => {
+                fold(target);
+                fold(value);
+            }
+            StatementKind::While { cond, body } => {
+                fold(cond);
+                block(body);
+            }
+            StatementKind::For { iterable, body, .. } => {
+                fold(iterable);
+                block(body);
+            }
+            StatementKind::Break | StatementKind::Continue => {}
+        }
+    }
+}
+
+fn diverges(body: &[Statement]) -> bool {
+    matches!(
+        body.last().map(|statement| &statement.kind),
+        Some(StatementKind::Return(_) | StatementKind::Break | StatementKind::Continue)
+    )
+}
+
+/// Drops the globals `main` does not use, directly or through other globals,
+/// unless computing them could have an effect. Keeps everything without a `main`.
+fn drop_unused(program: &mut Program) {
+    let globals: Vec<_> = (program.0.iter())
+        .filter_map(|item| match &item.kind {
+            ItemKind::DefItem { ident, expr, .. } => Some((ident.0, expr)),
+            _ => None,
+        })
+        .collect();
+    if !globals.iter().any(|(name, _)| *name == "main") {
+        return;
+    }
+    // the globals which are kept anyway keep the ones they use
+    let mut work: Vec<_> = (globals.iter())
+        .filter(|(name, expr)| *name == "main" || !is_value(expr))
+        .map(|(name, _)| *name)
+        .collect();
+    let mut used: HashSet<_> = work.iter().copied().collect();
+    while let Some(name) = work.pop() {
+        for (_, expr) in globals.iter().filter(|(global, _)| *global == name) {
+            let mut names = HashSet::new();
+            references(expr, &mut names);
+            work.extend(names.iter().filter(|name| !used.contains(*name)));
+            used.extend(names);
+        }
+    }
+    program.0.retain(|item| match &item.kind {
+        ItemKind::DefItem { ident, .. } => used.contains(ident.0),
+        _ => true,
+    });
+}
+
+/// Whether computing `expr` cannot fail nor have an effect.
+fn is_value(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::Literal(_) | ExprKind::Fn(_) | ExprKind::Path(..) => true,
+        ExprKind::Tuple(elements) | ExprKind::Array(elements) => elements.iter().all(is_value),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn optimized(input: &str, level: u8) -> String {
+        let mut program = parse_file(input).unwrap();
+        optimize(&mut program, level);
+        program.to_string()
+    }
+
+    #[test]
+    fn test_fold_literals() {
+        let folded = |expr: &str| optimized(&format!("let main = {};", expr), 1);
+        assert_eq!(folded("123 - 12 / 4"), "(Program (bind main 120))");
+        assert_eq!(folded("-(2 * 3) + 0.5"), "(Program (bind main -5.5))");
+        assert_eq!(
+            folded("\"a\" + \"b\" == \"ab\""),
+            "(Program (bind main true))"
+        );
+        assert_eq!(folded("1 == 1.0 && !(2 < 1)"), "(Program (bind main true))");
+        assert_eq!(folded("false || x"), "(Program (bind main x))");
+        assert_eq!(folded("x + 1 * 2"), "(Program (bind main (+ x 2)))");
+        assert_eq!(
+            folded("fn() { 2 * 3 }"),
+            "(Program (bind main (fn (params)(body (return 6)))))"
+        );
+    }
+
+    #[test]
+    fn test_fold_leaves_runtime_errors() {
+        let folded = |expr: &str| optimized(&format!("let main = {};", expr), 1);
+        assert_eq!(
+            folded("9223372036854775807 + 1"),
+            "(Program (bind main (+ 9223372036854775807 1)))"
+        );
+        assert_eq!(folded("1 / (1 - 1)"), "(Program (bind main (/ 1 0)))");
+        assert_eq!(folded("1..3"), "(Program (bind main (.. 1 3)))");
+    }
+
+    #[test]
+    fn test_unreachable_code() {
+        let input = r#"
+let f = fn(x) {
+    while true { break; x; };
+    if x { return 1; println("a"); } else { return 2; };
+    println("b");
+};"#;
+        assert_eq!(
+            optimized(input, 1),
+            "(Program (bind f (fn (params x)(body (while true (block (break))) \
+             (expr (if x (block (return 1)) (block (return 2)))) \
+             (expr (call println (args \"b\")))))))"
+        );
+    }
+
+    #[test]
+    fn test_drop_unused() {
+        let input = r#"
+let unused = fn() { 1 };
+let helper = fn() { 2 };
+let used = fn() { helper() };
+let effect = println(other());
+let other = fn() { "" };
+let main = used();"#;
+        let globals = |level| {
+            let mut program = parse_file(input).unwrap();
+            optimize(&mut program, level);
+            (program.0.iter())
+                .filter_map(|item| match &item.kind {
+                    ItemKind::DefItem { ident, .. } => Some(ident.0),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        assert_eq!(globals(1), "unused helper used effect other main");
+        assert_eq!(globals(2), "helper used effect other main");
+        // nothing is dropped without a main
+        let mut program = parse_file("let unused = 1;").unwrap();
+        optimize(&mut program, 2);
+        assert_eq!(program.0.len(), 1);
+    }
+}
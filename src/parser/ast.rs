@@ -1,13 +1,65 @@
+use std::borrow::Cow;
+
+use super::span::Span;
+
 #[derive(Debug)]
 pub struct Program<'a>(pub Vec<Item<'a>>);
 
+/// One line typed into the REPL.
+#[derive(Debug)]
+pub enum Line<'a> {
+    Item(Item<'a>),
+    Expr(Expr<'a>),
+}
+
+#[derive(Debug)]
+pub struct Item<'a> {
+    pub kind: ItemKind<'a>,
+    pub span: Span,
+}
+
 #[derive(Debug)]
-pub enum Item<'a> {
-    DefItem { ident: Ident<'a>, expr: Expr<'a> },
+pub enum ItemKind<'a> {
+    DefItem {
+        ident: Ident<'a>,
+        ty: Option<Type<'a>>,
+        expr: Expr<'a>,
+    },
+    /// `struct Point { x, y }`, or with types `struct Pair<T> { a: T, b: T }`
+    Struct {
+        ident: Ident<'a>,
+        generics: Vec<Ident<'a>>,
+        fields: Vec<Field<'a>>,
+    },
+    /// `enum Shape { Circle(r), Rect(w, h) }`
+    Enum {
+        ident: Ident<'a>,
+        variants: Vec<Variant<'a>>,
+    },
+}
+
+/// A field of a struct, with its optional type: `x: int`.
+#[derive(Debug)]
+pub struct Field<'a> {
+    pub ident: Ident<'a>,
+    pub ty: Option<Type<'a>>,
 }
 
+/// A variant of an enum, with the names of the values it holds.
 #[derive(Debug)]
-pub enum Expr<'a> {
+pub struct Variant<'a> {
+    pub ident: Ident<'a>,
+    pub fields: Vec<Ident<'a>>,
+}
+
+#[derive(Debug)]
+pub struct Expr<'a> {
+    pub kind: ExprKind<'a>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum ExprKind<'a> {
     Id(Ident<'a>),
     Literal(Literal<'a>),
     BinOp(Box<Expr<'a>>, Infix, Box<Expr<'a>>),
@@ -17,13 +69,53 @@ pub enum Expr<'a> {
         func: Box<Expr<'a>>,
         args: Vec<Expr<'a>>,
     },
+    If {
+        cond: Box<Expr<'a>>,
+        then: Block<'a>,
+        els: Option<Block<'a>>,
+    },
+    Index {
+        expr: Box<Expr<'a>>,
+        index: Box<Expr<'a>>,
+    },
+    Tuple(Vec<Expr<'a>>),
+    Array(Vec<Expr<'a>>),
+    /// `expr.0`
+    TupleField {
+        expr: Box<Expr<'a>>,
+        index: usize,
+    },
+    /// `expr.name`
+    Field {
+        expr: Box<Expr<'a>>,
+        field: Ident<'a>,
+    },
+    /// `Point { x: 1, y: 2 }`
+    Struct {
+        name: Ident<'a>,
+        fields: Vec<(Ident<'a>, Expr<'a>)>,
+    },
+    /// `Shape::Circle`, a variant of an enum. Called to give it its values.
+    Path(Ident<'a>, Ident<'a>),
+    Match {
+        expr: Box<Expr<'a>>,
+        arms: Vec<Arm<'a>>,
+    },
+    /// `expr[start..end]`, where both bounds are optional.
+    Slice {
+        expr: Box<Expr<'a>>,
+        start: Option<Box<Expr<'a>>>,
+        end: Option<Box<Expr<'a>>>,
+    },
 }
 
 #[derive(Debug)]
 pub enum Literal<'a> {
-    Number(f32),
+    Int(i64),
+    Float(f64),
     Boolean(bool),
-    String(&'a str),
+    /// With its escape sequences decoded.
+    String(Cow<'a, str>),
 }
 
 #[derive(Debug)]
@@ -32,25 +124,166 @@ pub enum Infix {
     Sub,
     Mul,
     Div,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+    /// `start..end`, up to but not including `end`.
+    Range,
 }
 
 #[derive(Debug)]
 pub enum Prefix {
     Neg,
+    Not,
 }
 
+/// What a `let` binds its value to, or what an arm of a `match` tests it against.
 #[derive(Debug)]
-pub struct Ident<'a>(pub &'a str);
+pub enum Pattern<'a> {
+    Id(Ident<'a>),
+    /// `(a, (b, c))`, taking a tuple of as many elements apart.
+    Tuple(Vec<Pattern<'a>>, Span),
+    /// `_`, matching anything without binding it.
+    Wildcard(Span),
+    Literal(Literal<'a>, Span),
+    /// `Shape::Rect(w, _)`
+    Variant {
+        enum_name: Ident<'a>,
+        variant: Ident<'a>,
+        fields: Vec<Pattern<'a>>,
+        span: Span,
+    },
+}
+
+/// `pattern => body`
+#[derive(Debug)]
+pub struct Arm<'a> {
+    pub pattern: Pattern<'a>,
+    pub body: Block<'a>,
+}
+
+#[derive(Debug)]
+pub struct Ident<'a>(pub &'a str, pub Span);
 
 #[derive(Debug)]
 pub struct Function<'a> {
-    pub params: Vec<Ident<'a>>,
+    /// The type parameters of `fn<T>`
+    pub generics: Vec<Ident<'a>>,
+    pub params: Vec<Param<'a>>,
+    /// `-> type`
+    pub ret: Option<Type<'a>>,
     pub body: Vec<Statement<'a>>, // todo: change to Statements
 }
 
+/// A parameter of a function, with its optional annotation: `n: int`.
+#[derive(Debug)]
+pub struct Param<'a> {
+    pub ident: Ident<'a>,
+    pub ty: Option<Type<'a>>,
+}
+
+/// A type written in an annotation.
+#[derive(Debug)]
+pub struct Type<'a> {
+    pub kind: TypeKind<'a>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum TypeKind<'a> {
+    /// `int`, `float`, `bool`, `string`, `range`, a struct or an enum, or a
+    /// type parameter, with the arguments of a generic type: `Pair<int, T>`.
+    Named(&'a str, Vec<Type<'a>>),
+    /// `(int, bool)`, and `()` for unit.
+    Tuple(Vec<Type<'a>>),
+    /// `[int]`, also written `Array<int>`
+    Array(Box<Type<'a>>),
+    /// `fn(int) -> bool`, returning unit without a return type.
+    Fn(Vec<Type<'a>>, Option<Box<Type<'a>>>),
+}
+
+/// Statements followed by an optional trailing expression, which is the value of the block.
+#[derive(Debug)]
+pub struct Block<'a> {
+    pub body: Vec<Statement<'a>>,
+    pub value: Option<Box<Expr<'a>>>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct Statement<'a> {
+    pub kind: StatementKind<'a>,
+    pub span: Span,
+}
+
 #[derive(Debug)]
-pub enum Statement<'a> {
-    Let(Ident<'a>, Box<Expr<'a>>),
+pub enum StatementKind<'a> {
+    /// `let pattern: type = value;`, where the type is optional.
+    Let(Pattern<'a>, Option<Type<'a>>, Box<Expr<'a>>),
     Set(Ident<'a>, Box<Expr<'a>>),
+    /// `target[index] = value;`
+    SetIndex {
+        target: Box<Expr<'a>>,
+        index: Box<Expr<'a>>,
+        value: Box<Expr<'a>>,
+    },
+    /// `target.field = value;`
+    SetField {
+        target: Box<Expr<'a>>,
+        field: Ident<'a>,
+        value: Box<Expr<'a>>,
+    },
     Return(Box<Expr<'a>>),
+    Expr(Box<Expr<'a>>),
+    While {
+        cond: Box<Expr<'a>>,
+        body: Block<'a>,
+    },
+    For {
+        pattern: Pattern<'a>,
+        iterable: Box<Expr<'a>>,
+        body: Block<'a>,
+    },
+    Break,
+    Continue,
+}
+
+impl<'a> Expr<'a> {
+    pub fn new(kind: ExprKind<'a>, span: Span) -> Self {
+        Expr { kind, span }
+    }
+}
+
+impl Pattern<'_> {
+    pub fn span(&self) -> Span {
+        match self {
+            Pattern::Id(Ident(_, span))
+            | Pattern::Tuple(_, span)
+            | Pattern::Wildcard(span)
+            | Pattern::Literal(_, span)
+            | Pattern::Variant { span, .. } => *span,
+        }
+    }
+}
+
+impl Span {
+    pub fn of(pair: &pest::iterators::Pair<'_, super::Rule>) -> Self {
+        Span {
+            start: pair.as_span().start(),
+            end: pair.as_span().end(),
+        }
+    }
+
+    /// The smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Self {
+        Span {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
 }
@@ -12,25 +12,121 @@ impl Display for Program<'_> {
     }
 }
 
-impl Display for Item<'_> {
+impl Display for Line<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Item::DefItem { ident, expr } => write!(f, "(bind {} {})", ident, expr),
+            Line::Item(item) => write!(f, "{}", item),
+            Line::Expr(expr) => write!(f, "{}", expr),
+        }
+    }
+}
+
+impl Display for Item<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            ItemKind::DefItem { ident, ty, expr } => {
+                write!(f, "(bind {} {})", annotated(ident, ty), expr)
+            }
+            ItemKind::Struct {
+                ident,
+                generics,
+                fields,
+            } => {
+                write!(f, "(struct {}{}", ident, display_generics(generics))?;
+                for Field { ident, ty } in fields {
+                    write!(f, " {}", annotated(ident, ty))?;
+                }
+                write!(f, ")")
+            }
+            ItemKind::Enum { ident, variants } => {
+                write!(f, "(enum {}", ident)?;
+                for Variant { ident, fields } in variants {
+                    match fields.is_empty() {
+                        true => write!(f, " {}", ident)?,
+                        false => {
+                            let fields: Vec<_> = fields.iter().map(|f| f.0).collect();
+                            write!(f, " ({} {})", ident, fields.join(" "))?
+                        }
+                    }
+                }
+                write!(f, ")")
+            }
         }
     }
 }
 
 impl Display for Expr<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            ExprKind::Id(Ident(name, _)) => write!(f, "{}", name),
+            ExprKind::Literal(literal) => write!(f, "{}", literal),
+            ExprKind::BinOp(lhs, op, rhs) => write!(f, "({} {} {})", op, lhs, rhs),
+            ExprKind::Prefix(op, rhs) => write!(f, "({} {})", op, rhs),
+            ExprKind::Fn(function) => write!(f, "{}", function),
+            ExprKind::Call { func, args } => {
+                write!(f, "(call {} (args{}))", func, display_args(args))
+            }
+            ExprKind::If { cond, then, els } => {
+                write!(f, "(if {} {}", cond, then)?;
+                if let Some(els) = els {
+                    write!(f, " {}", els)?;
+                }
+                write!(f, ")")
+            }
+            ExprKind::Index { expr, index } => write!(f, "(index {} {})", expr, index),
+            ExprKind::Tuple(elements) => write!(f, "(tuple{})", display_args(elements)),
+            ExprKind::Array(elements) => write!(f, "(array{})", display_args(elements)),
+            ExprKind::TupleField { expr, index } => write!(f, "(field {} {})", expr, index),
+            ExprKind::Field { expr, field } => write!(f, "(field {} {})", expr, field),
+            ExprKind::Path(enum_name, variant) => write!(f, "{}::{}", enum_name, variant),
+            ExprKind::Match { expr, arms } => {
+                write!(f, "(match {}", expr)?;
+                for Arm { pattern, body } in arms {
+                    write!(f, " ({} {})", pattern, body)?;
+                }
+                write!(f, ")")
+            }
+            ExprKind::Struct { name, fields } => {
+                write!(f, "(struct {}", name)?;
+                for (field, value) in fields {
+                    write!(f, " ({} {})", field, value)?;
+                }
+                write!(f, ")")
+            }
+            ExprKind::Slice { expr, start, end } => {
+                let bound = |bound: &Option<Box<Expr>>| match bound {
+                    Some(bound) => bound.to_string(),
+                    None => "_".to_string(),
+                };
+                write!(f, "(slice {} {} {})", expr, bound(start), bound(end))
+            }
+        }
+    }
+}
+
+impl Display for Pattern<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Expr::Id(Ident(name)) => write!(f, "{}", name),
-            Expr::Literal(Literal::Boolean(b)) => write!(f, "{}", b),
-            Expr::Literal(Literal::Number(n)) => write!(f, "{}", n),
-            Expr::Literal(Literal::String(s)) => write!(f, "{:?}", s),
-            Expr::BinOp(lhs, op, rhs) => write!(f, "({} {} {})", op, lhs, rhs),
-            Expr::Prefix(op, rhs) => write!(f, "({} {})", op, rhs),
-            Expr::Fn(function) => write!(f, "{}", function),
-            Expr::Call { func, args } => write!(f, "(call {} (args{}))", func, display_args(args)),
+            Pattern::Id(Ident(name, _)) => write!(f, "{}", name),
+            Pattern::Tuple(patterns, _) => {
+                let patterns: Vec<_> = patterns.iter().map(|p| p.to_string()).collect();
+                write!(f, "({})", patterns.join(" "))
+            }
+            Pattern::Wildcard(_) => write!(f, "_"),
+            Pattern::Literal(literal, _) => write!(f, "{}", literal),
+            Pattern::Variant {
+                enum_name,
+                variant,
+                fields,
+                ..
+            } => {
+                write!(f, "{}::{}", enum_name, variant)?;
+                if !fields.is_empty() {
+                    let fields: Vec<_> = fields.iter().map(|p| p.to_string()).collect();
+                    write!(f, "({})", fields.join(" "))?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -44,13 +140,16 @@ fn display_args(args: &[Expr]) -> String {
 
 impl Display for Function<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "(fn ")?;
+        write!(f, "(fn{} ", display_generics(&self.generics))?;
         // params
         write!(f, "(params")?;
         for p in self.params.iter() {
             write!(f, " {}", p)?;
         }
         write!(f, ")")?;
+        if let Some(ret) = &self.ret {
+            write!(f, "(returns {})", ret)?;
+        }
         // body
         write!(f, "(body")?;
         for st in self.body.iter() {
@@ -60,12 +159,106 @@ impl Display for Function<'_> {
     }
 }
 
+impl Display for Param<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", annotated(&self.ident, &self.ty))
+    }
+}
+
+impl Display for Type<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            TypeKind::Named(name, args) if args.is_empty() => write!(f, "{}", name),
+            TypeKind::Named(name, args) => write!(f, "{}<{}>", name, display_types(args)),
+            TypeKind::Tuple(types) if types.len() == 1 => write!(f, "({},)", types[0]),
+            TypeKind::Tuple(types) => write!(f, "({})", display_types(types)),
+            TypeKind::Array(ty) => write!(f, "[{}]", ty),
+            TypeKind::Fn(params, ret) => {
+                write!(f, "fn({})", display_types(params))?;
+                match ret {
+                    Some(ret) => write!(f, " -> {}", ret),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+fn display_types(types: &[Type]) -> String {
+    let types: Vec<_> = types.iter().map(|t| t.to_string()).collect();
+    types.join(", ")
+}
+
+/// `<T, U>`, or nothing without type parameters.
+pub fn display_generics(generics: &[Ident]) -> String {
+    match generics.is_empty() {
+        true => String::new(),
+        false => {
+            let generics: Vec<_> = generics.iter().map(|g| g.0).collect();
+            format!("<{}>", generics.join(", "))
+        }
+    }
+}
+
+/// `name: type`, or just `name` without an annotation.
+pub fn annotated(name: &impl Display, ty: &Option<Type>) -> String {
+    match ty {
+        Some(ty) => format!("{}: {}", name, ty),
+        None => name.to_string(),
+    }
+}
+
+impl Display for Block<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(block")?;
+        for st in self.body.iter() {
+            write!(f, " {}", st)?;
+        }
+        if let Some(value) = &self.value {
+            write!(f, " {}", value)?;
+        }
+        write!(f, ")")
+    }
+}
+
 impl Display for Statement<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            StatementKind::Let(pattern, ty, value) => {
+                write!(f, "(bind {} {})", annotated(pattern, ty), value)
+            }
+            StatementKind::Set(Ident(name, _), value) => write!(f, "(set {} {})", name, value),
+            StatementKind::SetIndex {
+                target,
+                index,
+                value,
+            } => write!(f, "(set (index {} {}) {})", target, index, value),
+            StatementKind::SetField {
+                target,
+                field,
+                value,
+            } => write!(f, "(set (field {} {}) {})", target, field, value),
+            StatementKind::Return(value) => write!(f, "(return {})", value),
+            StatementKind::Expr(value) => write!(f, "(expr {})", value),
+            StatementKind::While { cond, body } => write!(f, "(while {} {})", cond, body),
+            StatementKind::For {
+                pattern,
+                iterable,
+                body,
+            } => write!(f, "(for {} {} {})", pattern, iterable, body),
+            StatementKind::Break => write!(f, "(break)"),
+            StatementKind::Continue => write!(f, "(continue)"),
+        }
+    }
+}
+
+impl Display for Literal<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Statement::Let(Ident(name), value) => write!(f, "(bind {} {})", name, value),
-            Statement::Set(Ident(name), value) => write!(f, "(set {} {})", name, value),
-            Statement::Return(value) => write!(f, "(return {})", value),
+            Literal::Boolean(b) => write!(f, "{}", b),
+            Literal::Int(n) => write!(f, "{}", n),
+            Literal::Float(n) => write!(f, "{:?}", n),
+            Literal::String(s) => write!(f, "{:?}", s),
         }
     }
 }
@@ -77,6 +270,15 @@ impl Display for Infix {
             Infix::Sub => write!(f, "-"),
             Infix::Mul => write!(f, "*"),
             Infix::Div => write!(f, "/"),
+            Infix::Eq => write!(f, "=="),
+            Infix::Ne => write!(f, "!="),
+            Infix::Lt => write!(f, "<"),
+            Infix::Le => write!(f, "<="),
+            Infix::Gt => write!(f, ">"),
+            Infix::Ge => write!(f, ">="),
+            Infix::And => write!(f, "&&"),
+            Infix::Or => write!(f, "||"),
+            Infix::Range => write!(f, ".."),
         }
     }
 }
@@ -85,6 +287,7 @@ impl Display for Prefix {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Prefix::Neg => write!(f, "-"),
+            Prefix::Not => write!(f, "!"),
         }
     }
 }
@@ -108,4 +311,152 @@ mod tests {
         let a = parse_file(r#"let a = 12; let main = 42;"#).unwrap();
         assert_eq!(format!("{a}"), "(Program (bind a 12) (bind main 42))");
     }
+
+    #[test]
+    fn test_precedence() {
+        let a = parse_file(r#"let a = !x || 1 + 2 * 3 < 4 == y && -z >= 5;"#).unwrap();
+        assert_eq!(
+            format!("{a}"),
+            "(Program (bind a (|| (! x) (&& (== (< (+ 1 (* 2 3)) 4) y) (>= (- z) 5)))))"
+        );
+    }
+
+    #[test]
+    fn test_numbers() {
+        let a = parse_file(r#"let a = 12 + 12.0 + 1e3 + 0.5;"#).unwrap();
+        assert_eq!(
+            format!("{a}"),
+            "(Program (bind a (+ (+ (+ 12 12.0) 1000.0) 0.5)))"
+        );
+    }
+
+    #[test]
+    fn test_if() {
+        let a = parse_file(r#"let main = if a { 1 } else if b { 2 };"#).unwrap();
+        assert_eq!(
+            format!("{a}"),
+            "(Program (bind main (if a (block 1) (block (if b (block 2))))))"
+        );
+        let a = parse_file(r#"let f = fn() { if a { let b = 1; b; } else { }; 2 };"#).unwrap();
+        assert_eq!(
+            format!("{a}"),
+            "(Program (bind f (fn (params)(body (expr (if a (block (bind b 1) (expr b)) (block))) (return 2)))))"
+        );
+    }
+
+    #[test]
+    fn test_strings() {
+        let a = parse_file(r#"let s = "tab\there \"quoted\" \\ \/";"#).unwrap();
+        assert_eq!(
+            format!("{a}"),
+            r#"(Program (bind s "tab\there \"quoted\" \\ /"))"#
+        );
+        let a = parse_file(r#"let s = t[1] + t[1..2] + t[..n][0..] + t[..];"#).unwrap();
+        assert_eq!(
+            format!("{a}"),
+            "(Program (bind s (+ (+ (+ (index t 1) (slice t 1 2)) (slice (slice t _ n) 0 _)) (slice t _ _))))"
+        );
+        assert!(parse_file(r#"let s = "\q";"#).is_err());
+    }
+
+    #[test]
+    fn test_arrays() {
+        let a = parse_file(r#"let f = fn(a) { a[0][1] = [1, [], [2,]]; a = [a[0]]; };"#).unwrap();
+        assert_eq!(
+            format!("{a}"),
+            "(Program (bind f (fn (params a)(body (set (index (index a 0) 1) (array 1 (array) (array 2))) (set a (array (index a 0)))))))"
+        );
+    }
+
+    #[test]
+    fn test_tuples() {
+        let a = parse_file(r#"let t = ((1), (2,), (3, t.0.1,));"#).unwrap();
+        assert_eq!(
+            format!("{a}"),
+            "(Program (bind t (tuple 1 (tuple 2) (tuple 3 (field (field t 0) 1)))))"
+        );
+        let a = parse_file(r#"let f = fn(p) { let (x, (y, z)) = p; x };"#).unwrap();
+        assert_eq!(
+            format!("{a}"),
+            "(Program (bind f (fn (params p)(body (bind (x (y z)) p) (return x)))))"
+        );
+    }
+
+    #[test]
+    fn test_loops() {
+        let input = r#"let f = fn(n) { while n > 0 { n = n - 1; continue; }; for (i, c) in 0..n + 1 { break; } };"#;
+        let a = parse_file(input).unwrap();
+        assert_eq!(
+            format!("{a}"),
+            "(Program (bind f (fn (params n)(body (while (> n 0) (block (set n (- n 1)) (continue))) (for (i c) (.. 0 (+ n 1)) (block (break)))))))"
+        );
+        // keywords only end at a word boundary
+        let a = parse_file(r#"let index = fn(iffy, format) { iffy };"#).unwrap();
+        assert_eq!(
+            format!("{a}"),
+            "(Program (bind index (fn (params iffy format)(body (return iffy)))))"
+        );
+    }
+
+    #[test]
+    fn test_structs() {
+        let input = r#"struct P { x, y, } struct E {}
+let f = fn(p) { p.x.y = P { x: 1, y: p.0 }; if p.x { E {} } else { 0 } };"#;
+        let a = parse_file(input).unwrap();
+        assert_eq!(
+            format!("{a}"),
+            "(Program (struct P x y) (struct E) (bind f (fn (params p)(body (set (field (field p x) y) (struct P (x 1) (y (field p 0)))) (return (if (field p x) (block (struct E)) (block 0)))))))"
+        );
+        // a condition ends at the `{` of its block
+        let a = parse_file(r#"let f = fn(a) { while a { }; for x in a { }; };"#).unwrap();
+        assert_eq!(
+            format!("{a}"),
+            "(Program (bind f (fn (params a)(body (while a (block)) (for x a (block))))))"
+        );
+    }
+
+    #[test]
+    fn test_enums() {
+        let input = r#"enum Shape { Circle(r), Rect(w, h,), Empty }
+let f = fn(s) { match s { Shape::Circle(_) => 1, Shape::Rect(w, 2) => { w } x => Shape::Circle(x) } };"#;
+        let a = parse_file(input).unwrap();
+        assert_eq!(
+            format!("{a}"),
+            "(Program (enum Shape (Circle r) (Rect w h) Empty) (bind f (fn (params s)(body (return (match s (Shape::Circle(_) (block 1)) (Shape::Rect(w 2) (block w)) (x (block (call Shape::Circle (args x))))))))))"
+        );
+        let a = parse_file(r#"let f = fn(t) { let (_, b) = t; for _ in b { }; };"#).unwrap();
+        assert_eq!(
+            format!("{a}"),
+            "(Program (bind f (fn (params t)(body (bind (_ b) t) (for _ b (block))))))"
+        );
+    }
+
+    #[test]
+    fn test_annotations() {
+        let input = r#"let n: int = 1; let f = fn(a: [float], t: (int, (bool,)), u: ()) -> P { let (x, y): (P, P) = a; x };"#;
+        let a = parse_file(input).unwrap();
+        assert_eq!(
+            format!("{a}"),
+            "(Program (bind n: int 1) (bind f (fn (params a: [float] t: (int, (bool,)) u: ())(returns P)(body (bind (x y): (P, P) a) (return x)))))"
+        );
+        // a type in parentheses is not a tuple
+        let a = parse_file(r#"let n: ((int)) = 1;"#).unwrap();
+        assert_eq!(format!("{a}"), "(Program (bind n: int 1))");
+        assert!(parse_file(r#"let n: = 1;"#).is_err());
+        assert!(parse_file(r#"let f = fn() -> { 1 };"#).is_err());
+    }
+
+    #[test]
+    fn test_generics() {
+        let input = r#"struct Pair<A, B> { a: A, b: Array<B>, c } let map = fn<T, U>(f: fn(T) -> U, xs: [T], g: fn()) -> Pair<T, fn(int, U) -> bool> { xs };"#;
+        let a = parse_file(input).unwrap();
+        assert_eq!(
+            format!("{a}"),
+            "(Program (struct Pair<A, B> a: A b: Array<B> c) (bind map (fn<T, U> (params f: fn(T) -> U xs: [T] g: fn())(returns Pair<T, fn(int, U) -> bool>)(body (return xs)))))"
+        );
+        // `<` after a type is not a comparison
+        let a = parse_file(r#"let b: bool = x < y;"#).unwrap();
+        assert_eq!(format!("{a}"), "(Program (bind b: bool (< x y)))");
+        assert!(parse_file(r#"let f = fn<>() { 1 };"#).is_err());
+    }
 }
@@ -1,3 +1,4 @@
+use super::ast_display::{annotated, display_generics};
 use super::*;
 
 macro_rules! print_with_tab {
@@ -7,7 +8,6 @@ macro_rules! print_with_tab {
 }
 
 /// For pretty-printing AST nodes.
-
 pub fn pretty_print_program(program: &Program, depth: u8) {
     print_with_tab!(depth, "(Program");
     for item in &program.0 {
@@ -17,47 +17,146 @@ pub fn pretty_print_program(program: &Program, depth: u8) {
 }
 
 fn pretty_print_item(item: &Item, depth: u8) {
-    match item {
-        Item::DefItem {
-            ident: Ident(name),
-            expr,
-        } => {
-            print_with_tab!(depth, format!("(bind {}", name));
+    match &item.kind {
+        ItemKind::DefItem { ident, ty, expr } => {
+            print_with_tab!(depth, format!("(bind {}", annotated(ident, ty)));
             pretty_print_expr(expr, depth + 1);
             print_with_tab!(depth, ")");
         }
+        ItemKind::Struct {
+            ident,
+            generics,
+            fields,
+        } => {
+            let fs = fields.iter().fold(String::new(), |acc, f| {
+                acc + &format!(" {}", annotated(&f.ident, &f.ty))
+            });
+            let generics = display_generics(generics);
+            print_with_tab!(depth, format!("(struct {}{}{})", ident, generics, fs));
+        }
+        ItemKind::Enum { ident, variants } => {
+            print_with_tab!(depth, format!("(enum {}", ident));
+            for Variant { ident, fields } in variants {
+                let fs = fields
+                    .iter()
+                    .fold(String::new(), |acc, f| acc + &format!(" {}", f));
+                print_with_tab!(depth + 1, format!("({}{})", ident, fs));
+            }
+            print_with_tab!(depth, ")");
+        }
     }
 }
 
 fn pretty_print_expr(expr: &Expr, depth: u8) {
-    match expr {
-        Expr::Id(ident) => print_with_tab!(depth, ident),
-        Expr::Literal(Literal::Boolean(b)) => print_with_tab!(depth, b),
-        Expr::Literal(Literal::Number(n)) => print_with_tab!(depth, n),
-        Expr::Literal(Literal::String(s)) => print_with_tab!(depth, format!("{:?}", s)),
-        Expr::BinOp(lhs, op, rhs) => {
+    match &expr.kind {
+        ExprKind::Id(ident) => print_with_tab!(depth, ident),
+        ExprKind::Literal(Literal::Boolean(b)) => print_with_tab!(depth, b),
+        ExprKind::Literal(Literal::Int(n)) => print_with_tab!(depth, n),
+        ExprKind::Literal(Literal::Float(n)) => print_with_tab!(depth, format!("{:?}", n)),
+        ExprKind::Literal(Literal::String(s)) => print_with_tab!(depth, format!("{:?}", s)),
+        ExprKind::BinOp(lhs, op, rhs) => {
             print_with_tab!(depth, format!("({}", op));
             pretty_print_expr(lhs, depth + 1);
             pretty_print_expr(rhs, depth + 1);
             print_with_tab!(depth, ")");
         }
-        Expr::Prefix(op, rhs) => {
+        ExprKind::Prefix(op, rhs) => {
             print_with_tab!(depth, format!("({}", op));
             pretty_print_expr(rhs, depth + 1);
             print_with_tab!(depth, ")");
         }
-        Expr::Fn(Function { params, body }) => {
-            print_with_tab!(depth, "(fn");
+        ExprKind::Fn(Function {
+            generics,
+            params,
+            ret,
+            body,
+        }) => {
+            print_with_tab!(depth, format!("(fn{}", display_generics(generics)));
             pretty_print_params(params, depth + 1);
-            pretty_print_block(body, depth + 1);
+            if let Some(ret) = ret {
+                print_with_tab!(depth + 1, format!("(returns {})", ret));
+            }
+            pretty_print_block(body, None, depth + 1);
             print_with_tab!(depth, ")");
         }
-        Expr::Call { func, args } => {
+        ExprKind::Call { func, args } => {
             print_with_tab!(depth, "(call");
             pretty_print_expr(func, depth + 1);
             pretty_print_args(args, depth + 1);
             print_with_tab!(depth, ")");
         }
+        ExprKind::If { cond, then, els } => {
+            print_with_tab!(depth, "(if");
+            pretty_print_expr(cond, depth + 1);
+            pretty_print_block(&then.body, then.value.as_deref(), depth + 1);
+            if let Some(els) = els {
+                pretty_print_block(&els.body, els.value.as_deref(), depth + 1);
+            }
+            print_with_tab!(depth, ")");
+        }
+        ExprKind::Array(elements) => {
+            print_with_tab!(depth, "(array");
+            for element in elements {
+                pretty_print_expr(element, depth + 1);
+            }
+            print_with_tab!(depth, ")");
+        }
+        ExprKind::Tuple(elements) => {
+            print_with_tab!(depth, "(tuple");
+            for element in elements {
+                pretty_print_expr(element, depth + 1);
+            }
+            print_with_tab!(depth, ")");
+        }
+        ExprKind::TupleField { expr, index } => {
+            print_with_tab!(depth, format!("(field {}", index));
+            pretty_print_expr(expr, depth + 1);
+            print_with_tab!(depth, ")");
+        }
+        ExprKind::Field { expr, field } => {
+            print_with_tab!(depth, format!("(field {}", field));
+            pretty_print_expr(expr, depth + 1);
+            print_with_tab!(depth, ")");
+        }
+        ExprKind::Path(enum_name, variant) => {
+            print_with_tab!(depth, format!("{}::{}", enum_name, variant))
+        }
+        ExprKind::Match { expr, arms } => {
+            print_with_tab!(depth, "(match");
+            pretty_print_expr(expr, depth + 1);
+            for Arm { pattern, body } in arms {
+                print_with_tab!(depth + 1, format!("(arm {}", pattern));
+                pretty_print_block(&body.body, body.value.as_deref(), depth + 2);
+                print_with_tab!(depth + 1, ")");
+            }
+            print_with_tab!(depth, ")");
+        }
+        ExprKind::Struct { name, fields } => {
+            print_with_tab!(depth, format!("(struct {}", name));
+            for (field, value) in fields {
+                print_with_tab!(depth + 1, format!("({}", field));
+                pretty_print_expr(value, depth + 2);
+                print_with_tab!(depth + 1, ")");
+            }
+            print_with_tab!(depth, ")");
+        }
+        ExprKind::Index { expr, index } => {
+            print_with_tab!(depth, "(index");
+            pretty_print_expr(expr, depth + 1);
+            pretty_print_expr(index, depth + 1);
+            print_with_tab!(depth, ")");
+        }
+        ExprKind::Slice { expr, start, end } => {
+            print_with_tab!(depth, "(slice");
+            pretty_print_expr(expr, depth + 1);
+            for bound in [start, end] {
+                match bound {
+                    Some(bound) => pretty_print_expr(bound, depth + 1),
+                    None => print_with_tab!(depth + 1, "_"),
+                }
+            }
+            print_with_tab!(depth, ")");
+        }
     }
 }
 
@@ -69,37 +168,86 @@ fn pretty_print_args(args: &[Expr], depth: u8) {
     print_with_tab!(depth, ")");
 }
 
-fn pretty_print_params(params: &[Ident], depth: u8) {
+fn pretty_print_params(params: &[Param], depth: u8) {
     let ps = params
         .iter()
         .fold(String::new(), |acc, p| acc + &format!(" {}", p));
     print_with_tab!(depth, format!("(params{})", ps));
 }
-fn pretty_print_block(body: &[Statement], depth: u8) {
+fn pretty_print_block(body: &[Statement], value: Option<&Expr>, depth: u8) {
     print_with_tab!(depth, "(body");
     for statement in body {
         pretty_print_statement(statement, depth + 1);
     }
+    if let Some(value) = value {
+        pretty_print_expr(value, depth + 1);
+    }
     print_with_tab!(depth, ")");
 }
 
 fn pretty_print_statement(statement: &Statement, depth: u8) {
-    match statement {
-        Statement::Let(ident, expr) => {
-            print_with_tab!(depth, format!("(bind {}", ident));
+    match &statement.kind {
+        StatementKind::Let(pattern, ty, expr) => {
+            print_with_tab!(depth, format!("(bind {}", annotated(pattern, ty)));
             pretty_print_expr(expr, depth + 1);
             print_with_tab!(depth, ")");
         }
-        Statement::Return(expr) => {
+        StatementKind::Return(expr) => {
             print_with_tab!(depth, "(return");
             pretty_print_expr(expr, depth + 1);
             print_with_tab!(depth, ")");
         }
-        Statement::Set(ident, expr) => {
+        StatementKind::Set(ident, expr) => {
             print_with_tab!(depth, format!("(set {}", ident));
             pretty_print_expr(expr, depth + 1);
             print_with_tab!(depth, ")");
         }
+        StatementKind::SetIndex {
+            target,
+            index,
+            value,
+        } => {
+            print_with_tab!(depth, "(set (index");
+            pretty_print_expr(target, depth + 2);
+            pretty_print_expr(index, depth + 2);
+            print_with_tab!(depth + 1, ")");
+            pretty_print_expr(value, depth + 1);
+            print_with_tab!(depth, ")");
+        }
+        StatementKind::SetField {
+            target,
+            field,
+            value,
+        } => {
+            print_with_tab!(depth, format!("(set (field {}", field));
+            pretty_print_expr(target, depth + 2);
+            print_with_tab!(depth + 1, ")");
+            pretty_print_expr(value, depth + 1);
+            print_with_tab!(depth, ")");
+        }
+        StatementKind::Expr(expr) => {
+            print_with_tab!(depth, "(expr");
+            pretty_print_expr(expr, depth + 1);
+            print_with_tab!(depth, ")");
+        }
+        StatementKind::While { cond, body } => {
+            print_with_tab!(depth, "(while");
+            pretty_print_expr(cond, depth + 1);
+            pretty_print_block(&body.body, body.value.as_deref(), depth + 1);
+            print_with_tab!(depth, ")");
+        }
+        StatementKind::For {
+            pattern,
+            iterable,
+            body,
+        } => {
+            print_with_tab!(depth, format!("(for {}", pattern));
+            pretty_print_expr(iterable, depth + 1);
+            pretty_print_block(&body.body, body.value.as_deref(), depth + 1);
+            print_with_tab!(depth, ")");
+        }
+        StatementKind::Break => print_with_tab!(depth, "(break)"),
+        StatementKind::Continue => print_with_tab!(depth, "(continue)"),
     }
 }
 
@@ -1,13 +1,197 @@
 use std::fmt::Display;
 
+use pest::error::{Error, ErrorVariant, InputLocation};
+
+use super::span::{render_snippet, source_line, Position, Span};
+use super::Rule;
+
 #[derive(Debug)]
-pub enum ParserError {
-    Default(String),
+pub struct ParserError {
+    pub kind: ErrorKind,
+    pub start: Position,
+    pub end: Position,
+    /// The source line where the error starts, for showing a snippet.
+    pub line: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ErrorKind {
+    /// The input does not follow the grammar.
+    Syntax {
+        /// Human names of what could have come next, like "expression" or "`;`".
+        expected: Vec<String>,
+        found: String,
+    },
+    /// A literal follows the grammar, but its value cannot be represented.
+    InvalidLiteral(String),
+}
+
+impl ParserError {
+    pub fn new(kind: ErrorKind, source: &str, span: Span) -> Self {
+        let start = Position::of(source, span.start);
+        ParserError {
+            kind,
+            start,
+            end: Position::of(source, span.end),
+            line: source_line(source, start.line).to_string(),
+        }
+    }
+
+    pub fn from_pest(error: Error<Rule>, source: &str) -> Self {
+        let mut offset = match error.location {
+            InputLocation::Pos(pos) | InputLocation::Span((pos, _)) => pos,
+        };
+        let mut expected = Vec::new();
+        let attempts = error.parse_attempts();
+        match (&error.variant, &attempts) {
+            // the rules reported by pest failed before the farthest point reached
+            (_, Some(attempts)) if attempts.max_position > offset => {
+                offset = attempts.max_position;
+                expected.extend(
+                    attempts
+                        .expected_tokens()
+                        .iter()
+                        .filter_map(|token| describe_token(token, |_| true)),
+                );
+            }
+            (ErrorVariant::ParsingError { positives, .. }, _) => {
+                expected.extend(positives.iter().filter_map(describe_rule).map(String::from));
+                if let Some(attempts) = &attempts {
+                    expected.extend(
+                        attempts
+                            .expected_tokens()
+                            .iter()
+                            .filter_map(|token| describe_token(token, is_punctuation)),
+                    );
+                }
+            }
+            (ErrorVariant::CustomError { message }, _) => expected.push(message.clone()),
+        }
+        // an identifier is just one kind of expression
+        if expected.iter().any(|e| e == "expression") {
+            expected.retain(|e| e != "identifier");
+        }
+        expected.sort();
+        expected.dedup();
+
+        let found = found_at(source, offset);
+        let end = offset + found.len().min(1);
+        let found = match found {
+            "" => "end of input".to_string(),
+            token => format!("`{}`", token),
+        };
+        ParserError::new(
+            ErrorKind::Syntax { expected, found },
+            source,
+            Span { start: offset, end },
+        )
+    }
+
+    /// Renders the error with a caret-underlined snippet of the source,
+    /// pointing at `path` when the source comes from a file.
+    pub fn render(&self, path: Option<&str>) -> String {
+        let snippet = render_snippet(self.start, self.end, &self.line, path);
+        format!("error: {}\n{}", self.kind, snippet)
+    }
 }
+
 impl Display for ParserError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.render(None))
+    }
+}
+
+impl std::error::Error for ParserError {}
+
+impl Display for ErrorKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ParserError::Default(s) => write!(f, "{}", s),
+            ErrorKind::Syntax { expected, found } => match expected.split_last() {
+                None => write!(f, "unexpected {}", found),
+                Some((last, [])) => write!(f, "expected {}, found {}", last, found),
+                Some((last, rest)) => {
+                    write!(
+                        f,
+                        "expected {} or {}, found {}",
+                        rest.join(", "),
+                        last,
+                        found
+                    )
+                }
+            },
+            ErrorKind::InvalidLiteral(msg) => write!(f, "{}", msg),
         }
     }
 }
+
+/// The human name of a grammar rule, if it means anything to the user.
+fn describe_rule(rule: &Rule) -> Option<&'static str> {
+    match rule {
+        Rule::expr
+        | Rule::cond
+        | Rule::struct_lit
+        | Rule::number
+        | Rule::boolean
+        | Rule::string
+        | Rule::tuple
+        | Rule::array
+        | Rule::r#fn
+        | Rule::r#if
+        | Rule::r#match
+        | Rule::path
+        | Rule::neg
+        | Rule::not => Some("expression"),
+        Rule::ident => Some("identifier"),
+        Rule::named_type | Rule::tuple_type | Rule::array_type | Rule::fn_type => Some("type"),
+        Rule::add
+        | Rule::sub
+        | Rule::mul
+        | Rule::div
+        | Rule::eq
+        | Rule::ne
+        | Rule::le
+        | Rule::ge
+        | Rule::lt
+        | Rule::gt
+        | Rule::and
+        | Rule::or
+        | Rule::range => Some("operator"),
+        Rule::call => Some("`(`"),
+        Rule::index | Rule::slice | Rule::set_index => Some("`[`"),
+        Rule::field | Rule::set_field => Some("`.`"),
+        Rule::block => Some("`{`"),
+        Rule::let_stmt | Rule::repl_let => Some("`let`"),
+        Rule::EOI => Some("end of input"),
+        _ => None,
+    }
+}
+
+// pest does not export the type of expected tokens, only their display: the
+// text of literal tokens, `a..z` for char ranges and `BUILTIN_RULE`.
+fn describe_token(token: &impl Display, keep: impl Fn(&str) -> bool) -> Option<String> {
+    let token = token.to_string();
+    let chars: Vec<char> = token.chars().collect();
+    let is_range = chars.len() == 4 && chars[1] == '.' && chars[2] == '.';
+    match token.as_str() {
+        "BUILTIN_RULE" => None,
+        _ if is_range || token.trim().is_empty() || !keep(&token) => None,
+        _ => Some(format!("`{}`", token)),
+    }
+}
+
+/// Tokens closing or separating constructs, which no rule name covers.
+fn is_punctuation(token: &str) -> bool {
+    matches!(token, ";" | "," | ")" | "}" | "]" | "=" | ":")
+}
+
+/// The word or symbol at `offset`, empty at the end of the input.
+fn found_at(source: &str, offset: usize) -> &str {
+    let rest = &source[offset.min(source.len())..];
+    let word = rest
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(rest.len());
+    match word {
+        0 => rest.chars().next().map_or("", |c| &rest[..c.len_utf8()]),
+        n => &rest[..n],
+    }
+}
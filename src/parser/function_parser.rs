@@ -4,37 +4,244 @@ pub fn parse_function_def<'a>(
     mut pairs: Pairs<'a, Rule>,
     pratt: &PrattParser<Rule>,
 ) -> Function<'a> {
+    let generics = parse_generics(&mut pairs);
     let params = pairs
         .next()
         .unwrap()
-        .into_inner() // [ ident ]
-        .map(|arg| Ident(arg.as_str()))
+        .into_inner() // [ param ]
+        .map(|param| {
+            let mut pairs = param.into_inner(); // param > ident ~ type?
+            let ident = parse_ident(pairs.next().unwrap());
+            Param {
+                ident,
+                ty: parse_annotation(&mut pairs),
+            }
+        })
         .collect();
+    let ret = parse_annotation(&mut pairs);
 
-    let body = pairs
-        .next()
-        .unwrap()
-        .into_inner()
-        .map(|line| match line.as_rule() {
+    // the trailing expression of a function body is what it returns
+    let Block {
+        mut body, value, ..
+    } = parse_block(pairs.next().unwrap(), pratt);
+    if let Some(value) = value {
+        let span = value.span;
+        body.push(Statement {
+            kind: StatementKind::Return(value),
+            span,
+        });
+    }
+    Function {
+        generics,
+        params,
+        ret,
+        body,
+    }
+}
+
+pub fn parse_if<'a>(mut pairs: Pairs<'a, Rule>, pratt: &PrattParser<Rule>) -> ExprKind<'a> {
+    // if > expr ~ block ~ (block | if)?
+    let cond = pratt_parse(pairs.next().unwrap().into_inner(), pratt);
+    let then = parse_block(pairs.next().unwrap(), pratt);
+    let els = pairs.next().map(|els| match els.as_rule() {
+        Rule::block => parse_block(els, pratt),
+        Rule::r#if => {
+            let span = Span::of(&els);
+            let value = Expr::new(parse_if(els.into_inner(), pratt), span);
+            Block {
+                body: Vec::new(),
+                value: Some(Box::new(value)),
+                span,
+            }
+        }
+        p => unreachable!("get unexpected else branch: {p:?}"),
+    });
+    ExprKind::If {
+        cond: Box::new(cond),
+        then,
+        els,
+    }
+}
+
+pub fn parse_match<'a>(mut pairs: Pairs<'a, Rule>, pratt: &PrattParser<Rule>) -> ExprKind<'a> {
+    // match > cond ~ arm*
+    let expr = pratt_parse(pairs.next().unwrap().into_inner(), pratt);
+    let arms = pairs
+        .map(|arm| {
+            let mut pairs = arm.into_inner(); // arm > pattern ~ (block | expr)
+            let pattern = parse_pattern(pairs.next().unwrap());
+            let body = pairs.next().unwrap();
+            let body = match body.as_rule() {
+                Rule::block => parse_block(body, pratt),
+                _ => {
+                    let span = Span::of(&body);
+                    let value = pratt_parse(body.into_inner(), pratt);
+                    Block {
+                        body: Vec::new(),
+                        value: Some(Box::new(value)),
+                        span,
+                    }
+                }
+            };
+            Arm { pattern, body }
+        })
+        .collect();
+    ExprKind::Match {
+        expr: Box::new(expr),
+        arms,
+    }
+}
+
+pub fn parse_block<'a>(block: Pair<'a, Rule>, pratt: &PrattParser<Rule>) -> Block<'a> {
+    let span = Span::of(&block);
+    let mut body = Vec::new();
+    let mut value = None;
+    for line in block.into_inner() {
+        let span = Span::of(&line);
+        let kind = match line.as_rule() {
             Rule::let_stmt => {
-                let mut pairs = line.into_inner(); // let_stmt > ident ~ expr
-                let ident = pairs.next().unwrap().as_str();
+                let mut pairs = line.into_inner(); // let_stmt > ident ~ type? ~ expr
+                let ident = parse_ident(pairs.next().unwrap());
+                let ty = parse_annotation(&mut pairs);
                 let expr = pratt_parse(pairs.next().unwrap().into_inner(), pratt);
-                Statement::Let(Ident(ident), Box::new(expr))
+                StatementKind::Let(Pattern::Id(ident), ty, Box::new(expr))
             }
-            Rule::set_stmt => {
-                let mut pairs = line.into_inner(); // set_stmt > ident ~ expr
-                let ident = pairs.next().unwrap().as_str();
+            Rule::let_tuple => {
+                let mut pairs = line.into_inner(); // let_tuple > tuple_pattern ~ type? ~ expr
+                let pattern = parse_pattern(pairs.next().unwrap());
+                let ty = parse_annotation(&mut pairs);
                 let expr = pratt_parse(pairs.next().unwrap().into_inner(), pratt);
-                Statement::Set(Ident(ident), Box::new(expr))
+                StatementKind::Let(pattern, ty, Box::new(expr))
+            }
+            Rule::set_stmt => {
+                // set_stmt > ident ~ (set_index | set_field)* ~ expr
+                let mut pairs: Vec<_> = line.into_inner().collect();
+                let value = pratt_parse(pairs.pop().unwrap().into_inner(), pratt);
+                let mut pairs = pairs.into_iter();
+                let ident = parse_ident(pairs.next().unwrap());
+                parse_set(ident, pairs.collect(), value, pratt)
             }
-            Rule::ret_stmt => Statement::Return(Box::new(pratt_parse(
+            Rule::ret_stmt => StatementKind::Return(Box::new(pratt_parse(
                 line.into_inner().next().unwrap().into_inner(), // ret_stmt > expr
                 pratt,
             ))),
-            Rule::expr => Statement::Return(Box::new(pratt_parse(line.into_inner(), pratt))), // expr
-            p => unreachable!("get unexpected statement in function define: {p:?}"),
-        })
-        .collect();
-    Function { params, body }
+            Rule::expr_stmt => StatementKind::Expr(Box::new(pratt_parse(
+                line.into_inner().next().unwrap().into_inner(), // expr_stmt > expr
+                pratt,
+            ))),
+            Rule::while_stmt => {
+                let mut pairs = line.into_inner(); // while_stmt > expr ~ block
+                let cond = pratt_parse(pairs.next().unwrap().into_inner(), pratt);
+                let body = parse_block(pairs.next().unwrap(), pratt);
+                StatementKind::While {
+                    cond: Box::new(cond),
+                    body,
+                }
+            }
+            Rule::for_stmt => {
+                let mut pairs = line.into_inner(); // for_stmt > pattern ~ expr ~ block
+                let pattern = parse_pattern(pairs.next().unwrap());
+                let iterable = pratt_parse(pairs.next().unwrap().into_inner(), pratt);
+                let body = parse_block(pairs.next().unwrap(), pratt);
+                StatementKind::For {
+                    pattern,
+                    iterable: Box::new(iterable),
+                    body,
+                }
+            }
+            Rule::break_stmt => StatementKind::Break,
+            Rule::continue_stmt => StatementKind::Continue,
+            Rule::expr => {
+                value = Some(Box::new(pratt_parse(line.into_inner(), pratt))); // expr
+                continue;
+            }
+            p => unreachable!("get unexpected statement in block: {p:?}"),
+        };
+        body.push(Statement { kind, span });
+    }
+    Block { body, value, span }
+}
+
+/// `a = v;` sets a variable, `a[i][j] = v;` sets an element of `a[i]` and
+/// `a[i].x = v;` sets the field `x` of `a[i]`.
+fn parse_set<'a>(
+    ident: Ident<'a>,
+    mut accessors: Vec<Pair<'a, Rule>>,
+    value: Expr<'a>,
+    pratt: &PrattParser<Rule>,
+) -> StatementKind<'a> {
+    let Some(last) = accessors.pop() else {
+        return StatementKind::Set(ident, Box::new(value));
+    };
+    let span = ident.1;
+    let mut target = Expr::new(ExprKind::Id(ident), span);
+    for accessor in accessors {
+        let span = target.span.to(Span::of(&accessor));
+        let kind = match parse_accessor(accessor, pratt) {
+            Ok(index) => ExprKind::Index {
+                expr: Box::new(target),
+                index: Box::new(index),
+            },
+            Err(field) => ExprKind::Field {
+                expr: Box::new(target),
+                field,
+            },
+        };
+        target = Expr::new(kind, span);
+    }
+    match parse_accessor(last, pratt) {
+        Ok(index) => StatementKind::SetIndex {
+            target: Box::new(target),
+            index: Box::new(index),
+            value: Box::new(value),
+        },
+        Err(field) => StatementKind::SetField {
+            target: Box::new(target),
+            field,
+            value: Box::new(value),
+        },
+    }
+}
+
+/// The index of a `set_index`, or the name of a `set_field`.
+fn parse_accessor<'a>(
+    accessor: Pair<'a, Rule>,
+    pratt: &PrattParser<Rule>,
+) -> Result<Expr<'a>, Ident<'a>> {
+    let rule = accessor.as_rule();
+    let inner = accessor.into_inner().next().unwrap(); // [ expr ] or . ident
+    match rule {
+        Rule::set_index => Ok(pratt_parse(inner.into_inner(), pratt)),
+        _ => Err(parse_ident(inner)),
+    }
+}
+
+pub fn parse_pattern(pattern: Pair<'_, Rule>) -> Pattern<'_> {
+    let span = Span::of(&pattern);
+    match pattern.as_rule() {
+        Rule::ident => Pattern::Id(parse_ident(pattern)),
+        Rule::tuple_pattern => {
+            Pattern::Tuple(pattern.into_inner().map(parse_pattern).collect(), span)
+        }
+        Rule::wildcard => Pattern::Wildcard(span),
+        Rule::number => Pattern::Literal(parse_number(pattern.as_str()).unwrap(), span),
+        Rule::boolean => {
+            Pattern::Literal(Literal::Boolean(pattern.as_str().parse().unwrap()), span)
+        }
+        Rule::string => {
+            let inner = pattern.into_inner().next().unwrap().as_str();
+            Pattern::Literal(Literal::String(unescape(inner)), span)
+        }
+        Rule::variant_pattern => {
+            let mut pairs = pattern.into_inner(); // variant_pattern > path ~ pattern*
+            let (enum_name, variant) = parse_path(pairs.next().unwrap());
+            Pattern::Variant {
+                enum_name,
+                variant,
+                fields: pairs.map(parse_pattern).collect(),
+                span,
+            }
+        }
+        p => unreachable!("get unexpected pattern: {p:?}"),
+    }
 }
@@ -1,14 +1,110 @@
 use super::prelude::*;
 
 pub fn parse_item<'a>(item: Pair<'a, Rule>, pratt: &PrattParser<Rule>) -> Option<Item<'a>> {
+    let span = Span::of(&item);
     match item.as_rule() {
-        Rule::let_stmt => {
+        Rule::let_stmt | Rule::repl_let => {
             let mut pairs = item.into_inner();
-            let ident = Ident(pairs.next().unwrap().as_str());
+            let ident = parse_ident(pairs.next().unwrap());
+            let ty = parse_annotation(&mut pairs);
             let expr = pratt_parse(pairs.next().unwrap().into_inner(), pratt);
-            Some(Item::DefItem { ident, expr })
+            let kind = ItemKind::DefItem { ident, ty, expr };
+            Some(Item { kind, span })
+        }
+        Rule::struct_item => {
+            let mut pairs = item.into_inner(); // struct_item > ident ~ generics? ~ struct_field*
+            let ident = parse_ident(pairs.next().unwrap());
+            let generics = parse_generics(&mut pairs);
+            let fields = pairs
+                .map(|field| {
+                    let mut pairs = field.into_inner(); // struct_field > ident ~ type?
+                    let ident = parse_ident(pairs.next().unwrap());
+                    Field {
+                        ident,
+                        ty: parse_annotation(&mut pairs),
+                    }
+                })
+                .collect();
+            Some(Item {
+                kind: ItemKind::Struct {
+                    ident,
+                    generics,
+                    fields,
+                },
+                span,
+            })
+        }
+        Rule::enum_item => {
+            let mut pairs = item.into_inner(); // enum_item > ident ~ variant*
+            let ident = parse_ident(pairs.next().unwrap());
+            let variants = pairs
+                .map(|variant| {
+                    let mut pairs = variant.into_inner(); // variant > ident ~ ident*
+                    let ident = parse_ident(pairs.next().unwrap());
+                    let fields = pairs.map(parse_ident).collect();
+                    Variant { ident, fields }
+                })
+                .collect();
+            Some(Item {
+                kind: ItemKind::Enum { ident, variants },
+                span,
+            })
         }
         Rule::EOI => None,
         _ => unreachable!(),
     }
 }
+
+pub fn parse_ident(ident: Pair<'_, Rule>) -> Ident<'_> {
+    Ident(ident.as_str(), Span::of(&ident))
+}
+
+/// The enum and the variant of `Enum::Variant`.
+pub fn parse_path(path: Pair<'_, Rule>) -> (Ident<'_>, Ident<'_>) {
+    let mut pairs = path.into_inner(); // path > ident ~ ident
+    let enum_name = parse_ident(pairs.next().unwrap());
+    (enum_name, parse_ident(pairs.next().unwrap()))
+}
+
+pub fn parse_type(ty: Pair<'_, Rule>) -> Type<'_> {
+    let span = Span::of(&ty);
+    let kind = match ty.as_rule() {
+        Rule::named_type => {
+            let mut pairs = ty.into_inner(); // named_type > ident ~ type*
+            let name = pairs.next().unwrap().as_str();
+            TypeKind::Named(name, pairs.map(parse_type).collect())
+        }
+        Rule::tuple_type => TypeKind::Tuple(ty.into_inner().map(parse_type).collect()),
+        Rule::array_type => TypeKind::Array(Box::new(parse_type(ty.into_inner().next().unwrap()))),
+        Rule::fn_type => {
+            let mut pairs = ty.into_inner(); // fn_type > type_list ~ type?
+            let params = pairs.next().unwrap().into_inner().map(parse_type).collect();
+            TypeKind::Fn(params, pairs.next().map(|ret| Box::new(parse_type(ret))))
+        }
+        p => unreachable!("get unexpected type: {p:?}"),
+    };
+    Type { kind, span }
+}
+
+/// The type annotation at the front of `pairs`, if there is one.
+pub fn parse_annotation<'a>(pairs: &mut Pairs<'a, Rule>) -> Option<Type<'a>> {
+    match pairs.peek()?.as_rule() {
+        Rule::named_type | Rule::tuple_type | Rule::array_type | Rule::fn_type => {
+            pairs.next().map(parse_type)
+        }
+        _ => None,
+    }
+}
+
+/// The type parameters at the front of `pairs`, if there are any.
+pub fn parse_generics<'a>(pairs: &mut Pairs<'a, Rule>) -> Vec<Ident<'a>> {
+    match pairs.peek().map(|pair| pair.as_rule()) {
+        Some(Rule::generics) => pairs
+            .next()
+            .unwrap()
+            .into_inner()
+            .map(parse_ident)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
@@ -5,12 +5,14 @@ mod error;
 mod function_parser;
 mod item_parser;
 mod pratt_parser;
+mod span;
 
 mod prelude {
     pub use super::ast::*;
     pub use super::function_parser::*;
     pub use super::item_parser::*;
     pub use super::pratt_parser::*;
+    pub use super::span::Span;
     pub use super::Rule;
 
     pub use pest::{
@@ -26,27 +28,52 @@ use prelude::*;
 pub use ast::*;
 pub use ast_pretty_print::pretty_print_program;
 pub use error::*;
+pub use span::{render_snippet, source_line, Position, Span};
 
 #[derive(Parser)]
 #[grammar = "parser/grammar.pest"]
 struct SirenParser;
 
-pub fn parse_file(input: &str) -> Result<Program, ParserError> {
+pub fn parse_file(input: &str) -> Result<Program<'_>, ParserError> {
     let pratt = build_pratt_parser();
-    let items: Vec<Item> = SirenParser::parse(Rule::program, input)
-        .map_err(|e| ParserError::Default(format!("{}", e)))?
-        .filter_map(|p| parse_item(p, &pratt))
-        .collect();
+    let pairs = parse_source(Rule::program, input)?;
+    let items: Vec<Item> = pairs.filter_map(|p| parse_item(p, &pratt)).collect();
 
     Ok(Program(items))
 }
 
-// pub fn parse_line(input: &str) -> Result<Option<Item>, String> {
-//     let pratt = build_pratt_parser();
-//     SirenParser::parse(Rule::repl, input)
-//         .map(|mut pairs| parse_item(pairs.next().unwrap(), &pratt))
-//         .map_err(|e| format!("Parse error: {}", e))
-// }
+pub fn parse_line(input: &str) -> Result<Line<'_>, ParserError> {
+    let pratt = build_pratt_parser();
+    let line = parse_source(Rule::repl, input)?.next().unwrap();
+    match line.as_rule() {
+        Rule::repl_let | Rule::struct_item | Rule::enum_item => {
+            Ok(Line::Item(parse_item(line, &pratt).unwrap()))
+        }
+        Rule::expr => Ok(Line::Expr(pratt_parse(line.into_inner(), &pratt))),
+        p => unreachable!("get unexpected line in repl: {p:?}"),
+    }
+}
+
+/// Runs the grammar, then rejects literals it accepts but whose value is out of range.
+fn parse_source(rule: Rule, input: &str) -> Result<Pairs<'_, Rule>, ParserError> {
+    pest::set_error_detail(true);
+    let pairs = SirenParser::parse(rule, input).map_err(|e| ParserError::from_pest(e, input))?;
+    for pair in pairs.clone().flatten() {
+        if pair.as_rule() == Rule::number && parse_number(pair.as_str()).is_none() {
+            let span = Span {
+                start: pair.as_span().start(),
+                end: pair.as_span().end(),
+            };
+            let msg = format!("number `{}` does not fit in an int", pair.as_str());
+            return Err(ParserError::new(
+                ErrorKind::InvalidLiteral(msg),
+                input,
+                span,
+            ));
+        }
+    }
+    Ok(pairs)
+}
 
 #[cfg(test)]
 mod tests {
@@ -55,7 +82,7 @@ mod tests {
     fn test_input(input: &str) {
         match parse_file(input) {
             Ok(p) => println!("{:#?}", p),
-            Err(ParserError::Default(e)) => panic!("Error: {}", e),
+            Err(e) => panic!("Error: {}", e),
         }
     }
 
@@ -71,6 +98,102 @@ mod tests {
         test_input(input);
     }
 
+    fn syntax_error(input: &str) -> (Position, Vec<String>, String) {
+        let e = parse_file(input).unwrap_err();
+        let ErrorKind::Syntax { expected, found } = e.kind else {
+            panic!("Expected a syntax error, got {:?}", e.kind);
+        };
+        (e.start, expected, found)
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        let (at, expected, found) = syntax_error("let a = 1 + ;");
+        assert_eq!(at, Position { line: 1, col: 13 });
+        assert_eq!(expected, ["expression"]);
+        assert_eq!(found, "`;`");
+
+        let (at, expected, found) = syntax_error("let a = 1\nlet b = 2;");
+        assert_eq!(at, Position { line: 2, col: 1 });
+        assert_eq!(expected, ["`(`", "`.`", "`;`", "`[`", "operator"]);
+        assert_eq!(found, "`let`");
+
+        let (at, expected, _) = syntax_error("let = 1;");
+        assert_eq!(at, Position { line: 1, col: 5 });
+        assert_eq!(expected, ["identifier"]);
+
+        let (at, expected, _) = syntax_error("let f = fn(a b) { };");
+        assert_eq!(at, Position { line: 1, col: 14 });
+        assert_eq!(expected, ["`)`", "`,`", "`:`"]);
+
+        let (at, expected, found) = syntax_error("let a = \"abc");
+        assert_eq!(at, Position { line: 1, col: 13 });
+        assert_eq!(expected, ["`\"`"]);
+        assert_eq!(found, "end of input");
+    }
+
+    #[test]
+    fn test_invalid_literal() {
+        let e = parse_file("let a = 1;\nlet b = 99999999999999999999;").unwrap_err();
+        assert!(matches!(e.kind, ErrorKind::InvalidLiteral(_)));
+        assert_eq!(e.start, Position { line: 2, col: 9 });
+        assert_eq!(e.end, Position { line: 2, col: 29 });
+    }
+
+    #[test]
+    fn test_render_error() {
+        let e = parse_file("let a = 1;\nlet b = (a + ;").unwrap_err();
+        assert_eq!(
+            e.render(Some("main.siren")),
+            "error: expected expression, found `;`\n \
+              --> main.siren:2:14\n  \
+               |\n\
+             2 | let b = (a + ;\n  \
+               |              ^"
+        );
+    }
+
+    #[test]
+    fn test_spans() {
+        let input = "let a = 1;\nlet main = fn(x) {\n    x + a\n};";
+        let program = parse_file(input).unwrap();
+        let item = &program.0[1];
+        assert_eq!(&input[item.span.start..item.span.end], &input[11..]);
+        let ItemKind::DefItem { ident, expr, .. } = &item.kind else {
+            panic!("expected a definition, got {}", item);
+        };
+        assert_eq!(ident.1, Span { start: 15, end: 19 });
+        let ExprKind::Fn(function) = &expr.kind else {
+            panic!("expected a function, got {}", expr);
+        };
+        let StatementKind::Return(value) = &function.body[0].kind else {
+            panic!("expected the trailing value to be returned");
+        };
+        assert_eq!(&input[value.span.start..value.span.end], "x + a");
+        assert_eq!(
+            Position::of(input, value.span.start),
+            Position { line: 3, col: 5 }
+        );
+    }
+
+    #[test]
+    fn test_parse_line() {
+        for input in ["let a = 1", "let a = 1;"] {
+            let Ok(Line::Item(item)) = parse_line(input) else {
+                panic!("Expect an item from {:?}", input);
+            };
+            assert_eq!(format!("{item}"), "(bind a 1)");
+        }
+        for input in ["a + 1", "a + 1;"] {
+            let Ok(Line::Expr(expr)) = parse_line(input) else {
+                panic!("Expect an expression from {:?}", input);
+            };
+            assert_eq!(format!("{expr}"), "(+ a 1)");
+        }
+        assert!(parse_line("let a = ").is_err());
+        assert!(parse_line("a = 1").is_err());
+    }
+
     #[test]
     fn test_func_build() {
         let input = r#"
@@ -1,49 +1,211 @@
+use std::borrow::Cow;
+
 use super::prelude::*;
 
 pub fn build_pratt_parser() -> PrattParser<Rule> {
     PrattParser::new()
+        .op(Op::infix(Rule::range, Assoc::Left))
+        .op(Op::infix(Rule::or, Assoc::Left))
+        .op(Op::infix(Rule::and, Assoc::Left))
+        .op(Op::infix(Rule::eq, Assoc::Left) | Op::infix(Rule::ne, Assoc::Left))
+        .op(Op::infix(Rule::lt, Assoc::Left)
+            | Op::infix(Rule::le, Assoc::Left)
+            | Op::infix(Rule::gt, Assoc::Left)
+            | Op::infix(Rule::ge, Assoc::Left))
         .op(Op::infix(Rule::add, Assoc::Left) | Op::infix(Rule::sub, Assoc::Left))
         .op(Op::infix(Rule::mul, Assoc::Left) | Op::infix(Rule::div, Assoc::Left))
-        .op(Op::prefix(Rule::neg))
-        .op(Op::postfix(Rule::call))
+        .op(Op::prefix(Rule::neg) | Op::prefix(Rule::not))
+        .op(Op::postfix(Rule::call)
+            | Op::postfix(Rule::index)
+            | Op::postfix(Rule::slice)
+            | Op::postfix(Rule::field))
 }
 
 pub fn pratt_parse<'a>(expr: Pairs<'a, Rule>, pratt: &PrattParser<Rule>) -> Expr<'a> {
     pratt
-        .map_primary(|primary| match primary.as_rule() {
-            Rule::ident => Expr::Id(Ident(primary.as_str())),
-            Rule::number => Expr::Literal(Literal::Number(primary.as_str().parse().unwrap())),
-            Rule::boolean => Expr::Literal(Literal::Boolean(primary.as_str().parse().unwrap())),
-            Rule::r#fn => Expr::Fn(parse_function_def(primary.into_inner(), pratt)),
-            Rule::expr => pratt_parse(primary.into_inner(), pratt), // "(" ~ expr ~ ")"
-            Rule::string => Expr::Literal(Literal::String(
-                primary.into_inner().next().unwrap().as_str(),
-            )),
-            p => unreachable!("get unexpected primary in pratt: {p:?}"),
+        .map_primary(|primary| {
+            let span = Span::of(&primary);
+            let kind = match primary.as_rule() {
+                Rule::ident => ExprKind::Id(Ident(primary.as_str(), span)),
+                Rule::number => ExprKind::Literal(parse_number(primary.as_str()).unwrap()),
+                Rule::boolean => {
+                    ExprKind::Literal(Literal::Boolean(primary.as_str().parse().unwrap()))
+                }
+                Rule::r#fn => ExprKind::Fn(parse_function_def(primary.into_inner(), pratt)),
+                Rule::r#if => parse_if(primary.into_inner(), pratt),
+                Rule::r#match => parse_match(primary.into_inner(), pratt),
+                Rule::path => {
+                    let (enum_name, variant) = parse_path(primary);
+                    ExprKind::Path(enum_name, variant)
+                }
+                Rule::expr => return pratt_parse(primary.into_inner(), pratt), // "(" ~ expr ~ ")"
+                Rule::array => ExprKind::Array(
+                    primary
+                        .into_inner() // [ expr ]
+                        .map(|expr| pratt_parse(expr.into_inner(), pratt))
+                        .collect(),
+                ),
+                Rule::tuple => ExprKind::Tuple(
+                    primary
+                        .into_inner() // [ expr ]
+                        .map(|expr| pratt_parse(expr.into_inner(), pratt))
+                        .collect(),
+                ),
+                Rule::struct_lit => {
+                    let mut pairs = primary.into_inner(); // ident ~ field_init*
+                    let name = parse_ident(pairs.next().unwrap());
+                    let fields = pairs
+                        .map(|init| {
+                            let mut pairs = init.into_inner(); // field_init > ident ~ expr
+                            let field = parse_ident(pairs.next().unwrap());
+                            (
+                                field,
+                                pratt_parse(pairs.next().unwrap().into_inner(), pratt),
+                            )
+                        })
+                        .collect();
+                    ExprKind::Struct { name, fields }
+                }
+                Rule::string => ExprKind::Literal(Literal::String(unescape(
+                    primary.into_inner().next().unwrap().as_str(),
+                ))),
+                p => unreachable!("get unexpected primary in pratt: {p:?}"),
+            };
+            Expr::new(kind, span)
         })
-        .map_infix(|lhs, op, rhs| match op.as_rule() {
-            Rule::add => Expr::BinOp(Box::new(lhs), Infix::Add, Box::new(rhs)),
-            Rule::sub => Expr::BinOp(Box::new(lhs), Infix::Sub, Box::new(rhs)),
-            Rule::mul => Expr::BinOp(Box::new(lhs), Infix::Mul, Box::new(rhs)),
-            Rule::div => Expr::BinOp(Box::new(lhs), Infix::Div, Box::new(rhs)),
-            _ => unreachable!("get unexpected infix operator in pratt: {op:?}"),
+        .map_infix(|lhs, op, rhs| {
+            let op = match op.as_rule() {
+                Rule::add => Infix::Add,
+                Rule::sub => Infix::Sub,
+                Rule::mul => Infix::Mul,
+                Rule::div => Infix::Div,
+                Rule::eq => Infix::Eq,
+                Rule::ne => Infix::Ne,
+                Rule::lt => Infix::Lt,
+                Rule::le => Infix::Le,
+                Rule::gt => Infix::Gt,
+                Rule::ge => Infix::Ge,
+                Rule::and => Infix::And,
+                Rule::or => Infix::Or,
+                Rule::range => Infix::Range,
+                _ => unreachable!("get unexpected infix operator in pratt: {op:?}"),
+            };
+            let span = lhs.span.to(rhs.span);
+            Expr::new(ExprKind::BinOp(Box::new(lhs), op, Box::new(rhs)), span)
         })
-        .map_prefix(|op, rhs| match op.as_rule() {
-            Rule::neg => Expr::Prefix(Prefix::Neg, Box::new(rhs)),
-            _ => unreachable!("get unexpected prefix operator in pratt: {op:?}"),
+        .map_prefix(|op, rhs| {
+            let span = Span::of(&op).to(rhs.span);
+            let op = match op.as_rule() {
+                Rule::neg => Prefix::Neg,
+                Rule::not => Prefix::Not,
+                _ => unreachable!("get unexpected prefix operator in pratt: {op:?}"),
+            };
+            Expr::new(ExprKind::Prefix(op, Box::new(rhs)), span)
         })
-        .map_postfix(|lhs, op| match op.as_rule() {
-            Rule::call => {
-                let pairs = op.into_inner(); // [ expr ]
-                let args = pairs
-                    .map(|expr| pratt_parse(expr.into_inner(), pratt))
-                    .collect();
-                Expr::Call {
-                    func: Box::new(lhs),
-                    args,
+        .map_postfix(|lhs, op| {
+            let span = lhs.span.to(Span::of(&op));
+            let kind = match op.as_rule() {
+                Rule::call => {
+                    let pairs = op.into_inner(); // [ expr ]
+                    let args = pairs
+                        .map(|expr| pratt_parse(expr.into_inner(), pratt))
+                        .collect();
+                    ExprKind::Call {
+                        func: Box::new(lhs),
+                        args,
+                    }
+                }
+                Rule::index => {
+                    let index = op.into_inner().next().unwrap(); // [ expr ]
+                    let expr = Box::new(lhs);
+                    match pratt_parse(index.into_inner(), pratt) {
+                        // `a[i..j]` is a slice, as are `a[i..]` and `a[..j]`
+                        Expr {
+                            kind: ExprKind::BinOp(start, Infix::Range, end),
+                            ..
+                        } => ExprKind::Slice {
+                            expr,
+                            start: Some(start),
+                            end: Some(end),
+                        },
+                        index => ExprKind::Index {
+                            expr,
+                            index: Box::new(index),
+                        },
+                    }
+                }
+                Rule::field => {
+                    let field = op.into_inner().next().unwrap(); // field > field_index | ident
+                    match field.as_rule() {
+                        Rule::field_index => ExprKind::TupleField {
+                            expr: Box::new(lhs),
+                            index: field.as_str().parse().unwrap_or(usize::MAX),
+                        },
+                        _ => ExprKind::Field {
+                            expr: Box::new(lhs),
+                            field: parse_ident(field),
+                        },
+                    }
                 }
-            }
-            _ => unreachable!("get unexpected postfix operator in pratt: {op:?}"),
+                Rule::slice => {
+                    let (mut start, mut end) = (None, None);
+                    let mut seen_dots = false;
+                    for pair in op.into_inner() {
+                        match pair.as_rule() {
+                            Rule::dots => seen_dots = true,
+                            _ => {
+                                let bound = Box::new(pratt_parse(pair.into_inner(), pratt));
+                                match seen_dots {
+                                    false => start = Some(bound),
+                                    true => end = Some(bound),
+                                }
+                            }
+                        }
+                    }
+                    ExprKind::Slice {
+                        expr: Box::new(lhs),
+                        start,
+                        end,
+                    }
+                }
+                _ => unreachable!("get unexpected postfix operator in pratt: {op:?}"),
+            };
+            Expr::new(kind, span)
         })
         .parse(expr)
 }
+
+/// Decodes the escape sequences of the `char` rule. Borrows when there are none.
+pub fn unescape(inner: &str) -> Cow<'_, str> {
+    if !inner.contains('\\') {
+        return Cow::Borrowed(inner);
+    }
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        // the grammar only lets through the escapes below
+        out.push(match chars.next().unwrap() {
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            c => c, // `"`, `\` and `/` stand for themselves
+        });
+    }
+    Cow::Owned(out)
+}
+
+/// Numbers with a fraction or an exponent are floats, all others are integers.
+/// Returns `None` for integers out of the range of `int`.
+pub fn parse_number(number: &str) -> Option<Literal<'_>> {
+    if number.contains(['.', 'e', 'E']) {
+        number.parse().ok().map(Literal::Float)
+    } else {
+        number.parse().ok().map(Literal::Int)
+    }
+}
@@ -0,0 +1,63 @@
+/// A range of bytes in the source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A 1-based line and column in the source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Position {
+    /// Finds the line and column of the byte at `offset`, counting columns in chars.
+    pub fn of(source: &str, offset: usize) -> Self {
+        let before = &source[..offset.min(source.len())];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Position {
+            line: before.matches('\n').count() + 1,
+            col: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+impl std::fmt::Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+/// The text of the `line`-th line of the source, without its line break.
+pub fn source_line(source: &str, line: usize) -> &str {
+    source
+        .lines()
+        .nth(line - 1)
+        .unwrap_or_default()
+        .trim_end_matches('\r')
+}
+
+/// Renders the location of `start` with a caret-underlined snippet of `line`,
+/// the source line where it is, pointing at `path` when the source comes from a file.
+pub fn render_snippet(start: Position, end: Position, line: &str, path: Option<&str>) -> String {
+    let number = start.line.to_string();
+    let pad = " ".repeat(number.len());
+    let width = match end.line == start.line {
+        true => end.col.saturating_sub(start.col).max(1),
+        false => 1,
+    };
+    let location = match path {
+        Some(path) => format!("{}:{}", path, start),
+        None => start.to_string(),
+    };
+    format!(
+        "{pad}--> {}\n{pad} |\n{} | {}\n{pad} | {}{}",
+        location,
+        number,
+        line,
+        " ".repeat(start.col - 1),
+        "^".repeat(width),
+    )
+}
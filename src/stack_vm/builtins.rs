@@ -0,0 +1,309 @@
+use std::{
+    cmp::Ordering,
+    io::{self, BufRead, Write},
+};
+
+use super::*;
+
+/// Functions every program starts with, bound as globals.
+pub fn builtins() -> Vec<NativeFn> {
+    vec![
+        NativeFn::variadic("print", 0, |args| write_values(&mut io::stdout(), args, "")),
+        NativeFn::variadic("println", 0, |args| {
+            write_values(&mut io::stdout(), args, "\n")
+        }),
+        NativeFn::new("read_line", 0, |_| read_line(&mut io::stdin().lock())),
+        NativeFn::new("len", 1, len),
+        NativeFn::new("push", 2, push),
+        NativeFn::new("pop", 1, pop),
+        NativeFn::new("abs", 1, abs),
+        NativeFn::new("min", 2, |args| pick(args, Ordering::Greater)),
+        NativeFn::new("max", 2, |args| pick(args, Ordering::Less)),
+        (|x: f64| x.sqrt()).into_native("sqrt"),
+        (|x: f64| x.floor()).into_native("floor"),
+        NativeFn::variadic("concat", 0, |args| Ok(Value::String(join(args, "").into()))),
+        NativeFn::variadic("format", 1, format),
+        NativeFn::new("str", 1, |args| Ok(Value::String(text(&args[0]).into()))),
+        NativeFn::new("int", 1, int),
+        NativeFn::new("float", 1, float),
+    ]
+}
+
+/// How a value is printed: strings without their quotes.
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.to_string(),
+        value => value.to_string(),
+    }
+}
+
+fn join(values: &[Value], separator: &str) -> String {
+    let texts: Vec<_> = values.iter().map(text).collect();
+    texts.join(separator)
+}
+
+fn io_error(error: io::Error) -> RuntimeError {
+    RuntimeError::Native(format!("I/O error: {}", error))
+}
+
+/// Writes the values separated by spaces, then `end`.
+fn write_values(out: &mut impl Write, args: &[Value], end: &str) -> Result<Value, RuntimeError> {
+    write!(out, "{}{}", join(args, " "), end).map_err(io_error)?;
+    out.flush().map_err(io_error)?;
+    Ok(Value::Unit)
+}
+
+/// Reads a line without its line break. It is empty at the end of the input.
+fn read_line(input: &mut impl BufRead) -> Result<Value, RuntimeError> {
+    let mut line = String::new();
+    input.read_line(&mut line).map_err(io_error)?;
+    let len = line.trim_end_matches(['\n', '\r']).len();
+    line.truncate(len);
+    Ok(Value::String(line.into()))
+}
+
+/// The number of characters of a string, or of elements of an array or a range.
+fn len(args: &[Value]) -> Result<Value, RuntimeError> {
+    match &args[0] {
+        Value::String(s) => Ok(Value::Int(s.chars().count() as i64)),
+        Value::Array(array) => Ok(Value::Int(array.borrow().len() as i64)),
+        Value::Range(start, end) => Ok(Value::Int(end.saturating_sub(*start).max(0))),
+        v => Err(RuntimeError::TypeMismatch(format!(
+            "Cannot take the length of {}",
+            v
+        ))),
+    }
+}
+
+fn push(args: &[Value]) -> Result<Value, RuntimeError> {
+    match &args[0] {
+        Value::Array(array) => {
+            array.borrow_mut().push(args[1].clone());
+            Ok(Value::Unit)
+        }
+        v => Err(RuntimeError::TypeMismatch(format!("Cannot push to {}", v))),
+    }
+}
+
+/// Removes and returns the last element of an array.
+fn pop(args: &[Value]) -> Result<Value, RuntimeError> {
+    match &args[0] {
+        Value::Array(array) => array
+            .borrow_mut()
+            .pop()
+            .ok_or_else(|| RuntimeError::Native("Cannot pop from an empty array".to_string())),
+        v => Err(RuntimeError::TypeMismatch(format!("Cannot pop from {}", v))),
+    }
+}
+
+fn abs(args: &[Value]) -> Result<Value, RuntimeError> {
+    match &args[0] {
+        Value::Int(n) => n
+            .checked_abs()
+            .map(Value::Int)
+            .ok_or_else(|| RuntimeError::IntegerOverflow(format!("abs({})", n))),
+        Value::Float(n) => Ok(Value::Float(n.abs())),
+        v => Err(RuntimeError::TypeMismatch(format!(
+            "Expect Number, found {}",
+            v
+        ))),
+    }
+}
+
+/// `min` and `max`: the second argument when the first compares as `replace`
+/// to it, else the first. Two ints give an int, anything else is promoted to floats.
+fn pick(args: &[Value], replace: Ordering) -> Result<Value, RuntimeError> {
+    match (&args[0], &args[1]) {
+        (Value::Int(a), Value::Int(b)) => Ok(Value::Int(match a.cmp(b) == replace {
+            true => *b,
+            false => *a,
+        })),
+        (a, b) => {
+            let a = f64::from_value(a.clone())?;
+            let b = f64::from_value(b.clone())?;
+            Ok(Value::Float(match a.partial_cmp(&b) == Some(replace) {
+                true => b,
+                false => a,
+            }))
+        }
+    }
+}
+
+/// Replaces each `{}` of the template by the text of the next argument.
+fn format(args: &[Value]) -> Result<Value, RuntimeError> {
+    let Value::String(template) = &args[0] else {
+        return Err(RuntimeError::TypeMismatch(format!(
+            "Expect a string template, found {}",
+            args[0]
+        )));
+    };
+    let pieces: Vec<_> = template.split("{}").collect();
+    let values = &args[1..];
+    if pieces.len() - 1 != values.len() {
+        return Err(RuntimeError::Native(format!(
+            "The template has {} placeholders but {} values were given",
+            pieces.len() - 1,
+            values.len()
+        )));
+    }
+    let mut out = pieces[0].to_string();
+    for (value, piece) in values.iter().zip(&pieces[1..]) {
+        out += &text(value);
+        out += piece;
+    }
+    Ok(Value::String(out.into()))
+}
+
+/// Converts to an integer, truncating floats toward zero.
+fn int(args: &[Value]) -> Result<Value, RuntimeError> {
+    match &args[0] {
+        Value::Int(n) => Ok(Value::Int(*n)),
+        Value::Float(n) => {
+            let truncated = n.trunc();
+            // i64::MAX is not a float, but 2^63 is the first one out of range
+            if truncated.is_nan() || truncated < i64::MIN as f64 || truncated >= i64::MAX as f64 {
+                return Err(RuntimeError::IntegerOverflow(format!(
+                    "{:?} does not fit in an int",
+                    n
+                )));
+            }
+            Ok(Value::Int(truncated as i64))
+        }
+        Value::Bool(b) => Ok(Value::Int(*b as i64)),
+        Value::String(s) => s
+            .trim()
+            .parse()
+            .map(Value::Int)
+            .map_err(|_| RuntimeError::Native(format!("Cannot parse {:?} as an int", s))),
+        v => Err(RuntimeError::TypeMismatch(format!(
+            "Cannot convert {} to int",
+            v
+        ))),
+    }
+}
+
+fn float(args: &[Value]) -> Result<Value, RuntimeError> {
+    match &args[0] {
+        Value::Int(n) => Ok(Value::Float(*n as f64)),
+        Value::Float(n) => Ok(Value::Float(*n)),
+        Value::String(s) => s
+            .trim()
+            .parse()
+            .map(Value::Float)
+            .map_err(|_| RuntimeError::Native(format!("Cannot parse {:?} as a float", s))),
+        v => Err(RuntimeError::TypeMismatch(format!(
+            "Cannot convert {} to float",
+            v
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(name: &str, args: &[Value]) -> Result<Value, RuntimeError> {
+        let native = builtins().into_iter().find(|n| n.name == name).unwrap();
+        (native.func)(args)
+    }
+
+    fn string(s: &str) -> Value {
+        Value::String(s.into())
+    }
+
+    #[test]
+    fn test_print() {
+        let mut out = Vec::new();
+        let args = [string("x ="), Value::Int(1), Value::Float(2.0)];
+        write_values(&mut out, &args, "\n").unwrap();
+        write_values(&mut out, &[], "").unwrap();
+        write_values(&mut out, &[Value::Bool(true)], "").unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "x = 1 2.0\ntrue");
+    }
+
+    #[test]
+    fn test_read_line() {
+        let mut input = "first\r\nsecond".as_bytes();
+        assert_eq!(read_line(&mut input).unwrap(), string("first"));
+        assert_eq!(read_line(&mut input).unwrap(), string("second"));
+        assert_eq!(read_line(&mut input).unwrap(), string(""));
+    }
+
+    #[test]
+    fn test_len() {
+        assert_eq!(call("len", &[string("héllo")]).unwrap(), Value::Int(5));
+        assert_eq!(call("len", &[Value::Range(2, 5)]).unwrap(), Value::Int(3));
+        assert_eq!(call("len", &[Value::Range(5, 2)]).unwrap(), Value::Int(0));
+        assert!(call("len", &[Value::Int(1)]).is_err());
+    }
+
+    #[test]
+    fn test_arrays() {
+        let array = [Value::array(vec![Value::Int(1)])];
+        let args = [array[0].clone(), string("x")];
+        assert_eq!(call("push", &args).unwrap(), Value::Unit);
+        assert_eq!(call("len", &array).unwrap(), Value::Int(2));
+        assert_eq!(call("pop", &array).unwrap(), string("x"));
+        assert_eq!(call("pop", &array).unwrap(), Value::Int(1));
+        assert!(matches!(call("pop", &array), Err(RuntimeError::Native(_))));
+        assert!(call("push", &[string("s"), Value::Int(1)]).is_err());
+    }
+
+    #[test]
+    fn test_math() {
+        assert_eq!(call("abs", &[Value::Int(-3)]).unwrap(), Value::Int(3));
+        assert_eq!(
+            call("abs", &[Value::Float(-0.5)]).unwrap(),
+            Value::Float(0.5)
+        );
+        assert!(matches!(
+            call("abs", &[Value::Int(i64::MIN)]),
+            Err(RuntimeError::IntegerOverflow(_))
+        ));
+        let (one, two) = (Value::Int(1), Value::Int(2));
+        assert_eq!(call("min", &[two.clone(), one.clone()]).unwrap(), one);
+        assert_eq!(call("max", &[two.clone(), one.clone()]).unwrap(), two);
+        assert_eq!(
+            call("min", &[Value::Float(1.5), two]).unwrap(),
+            Value::Float(1.5)
+        );
+        assert_eq!(
+            call("max", &[one, Value::Float(1.5)]).unwrap(),
+            Value::Float(1.5)
+        );
+        assert_eq!(call("sqrt", &[Value::Int(9)]).unwrap(), Value::Float(3.0));
+        assert_eq!(
+            call("floor", &[Value::Float(-1.5)]).unwrap(),
+            Value::Float(-2.0)
+        );
+        assert!(matches!(
+            call("sqrt", &[string("9")]),
+            Err(RuntimeError::BadArgument { .. })
+        ));
+    }
+
+    #[test]
+    fn test_strings() {
+        let args = [string("a"), Value::Int(1), Value::Bool(false)];
+        assert_eq!(call("concat", &args).unwrap(), string("a1false"));
+        let args = [
+            string("{} + {} = {}"),
+            Value::Int(1),
+            Value::Float(0.5),
+            string("1.5"),
+        ];
+        assert_eq!(call("format", &args).unwrap(), string("1 + 0.5 = 1.5"));
+        assert!(call("format", &[string("{} {}"), Value::Int(1)]).is_err());
+        assert!(call("format", &[Value::Int(1)]).is_err());
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(call("str", &[Value::Float(2.0)]).unwrap(), string("2.0"));
+        assert_eq!(call("str", &[string("s")]).unwrap(), string("s"));
+        assert_eq!(call("int", &[string(" 42 ")]).unwrap(), Value::Int(42));
+        assert_eq!(call("int", &[Value::Float(-2.7)]).unwrap(), Value::Int(-2));
+        assert_eq!(call("float", &[string("2.5")]).unwrap(), Value::Float(2.5));
+        assert!(call("int", &[string("4x")]).is_err());
+    }
+}
@@ -0,0 +1,221 @@
+use super::*;
+
+/// Rust types a Siren value can be turned into.
+pub trait FromValue: Sized {
+    fn from_value(value: Value) -> Result<Self, RuntimeError>;
+}
+
+/// Rust types that can be handed to Siren as a value.
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+/// What a native function may return: a value, or a `Result` of one.
+pub trait NativeResult {
+    fn into_result(self) -> Result<Value, RuntimeError>;
+}
+
+/// Rust closures that can be called from Siren, taking up to four `FromValue`
+/// arguments and returning a `NativeResult`.
+pub trait IntoNative<Args> {
+    fn into_native(self, name: &str) -> NativeFn;
+}
+
+/// Arguments of a call from the host: a tuple of `IntoValue`s, or a `Vec<Value>`.
+pub trait IntoArgs {
+    fn into_args(self) -> Vec<Value>;
+}
+
+fn mismatch(expected: &str, value: &Value) -> RuntimeError {
+    RuntimeError::TypeMismatch(format!("Expect {}, found {}", expected, value))
+}
+
+impl FromValue for Value {
+    fn from_value(value: Value) -> Result<Self, RuntimeError> {
+        Ok(value)
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: Value) -> Result<Self, RuntimeError> {
+        match value {
+            Value::Int(n) => Ok(n),
+            value => Err(mismatch("int", &value)),
+        }
+    }
+}
+
+/// Ints are promoted, as in the language's arithmetic.
+impl FromValue for f64 {
+    fn from_value(value: Value) -> Result<Self, RuntimeError> {
+        match value {
+            Value::Int(n) => Ok(n as f64),
+            Value::Float(n) => Ok(n),
+            value => Err(mismatch("float", &value)),
+        }
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: Value) -> Result<Self, RuntimeError> {
+        match value {
+            Value::Bool(b) => Ok(b),
+            value => Err(mismatch("bool", &value)),
+        }
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: Value) -> Result<Self, RuntimeError> {
+        match value {
+            Value::String(s) => Ok(s.to_string()),
+            value => Err(mismatch("string", &value)),
+        }
+    }
+}
+
+impl FromValue for () {
+    fn from_value(value: Value) -> Result<Self, RuntimeError> {
+        match value {
+            Value::Unit => Ok(()),
+            value => Err(mismatch("unit", &value)),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: Value) -> Result<Self, RuntimeError> {
+        match value {
+            Value::Array(array) => array.borrow().iter().cloned().map(T::from_value).collect(),
+            value => Err(mismatch("array", &value)),
+        }
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+impl IntoValue for i64 {
+    fn into_value(self) -> Value {
+        Value::Int(self)
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Value {
+        Value::Float(self)
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Value {
+        Value::Bool(self)
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Value {
+        Value::String(self.into())
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Value {
+        Value::String(self.into())
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Value {
+        Value::Unit
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Value {
+        Value::array(self.into_iter().map(IntoValue::into_value).collect())
+    }
+}
+
+impl IntoArgs for Vec<Value> {
+    fn into_args(self) -> Vec<Value> {
+        self
+    }
+}
+
+macro_rules! tuple_args {
+    ($($arg:ident),*) => {
+        impl<$($arg: IntoValue),*> IntoArgs for ($($arg,)*) {
+            #[allow(non_snake_case)]
+            fn into_args(self) -> Vec<Value> {
+                let ($($arg,)*) = self;
+                vec![$($arg.into_value()),*]
+            }
+        }
+    };
+}
+
+tuple_args!();
+tuple_args!(A);
+tuple_args!(A, B);
+tuple_args!(A, B, C);
+tuple_args!(A, B, C, D);
+
+impl<T: IntoValue> NativeResult for T {
+    fn into_result(self) -> Result<Value, RuntimeError> {
+        Ok(self.into_value())
+    }
+}
+
+impl<T: IntoValue> NativeResult for Result<T, RuntimeError> {
+    fn into_result(self) -> Result<Value, RuntimeError> {
+        self.map(IntoValue::into_value)
+    }
+}
+
+fn bad_argument(function: &str, index: usize, error: RuntimeError) -> RuntimeError {
+    let msg = match error {
+        RuntimeError::TypeMismatch(msg) => msg,
+        error => error.to_string(),
+    };
+    RuntimeError::BadArgument {
+        function: function.to_string(),
+        index: index + 1,
+        msg,
+    }
+}
+
+macro_rules! native_fn {
+    ($($arg:ident),*) => {
+        impl<F, R, $($arg: FromValue),*> IntoNative<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + 'static,
+            R: NativeResult,
+        {
+            #[allow(non_snake_case, unused_mut, unused_variables)]
+            fn into_native(self, name: &str) -> NativeFn {
+                let arity = <[&str]>::len(&[$(stringify!($arg)),*]);
+                let function = name.to_string();
+                NativeFn::new(name, arity, move |args: &[Value]| {
+                    // the VM checked the arity already
+                    let mut args = args.iter().cloned().enumerate();
+                    $(
+                        let (index, value) = args.next().unwrap();
+                        let $arg = $arg::from_value(value)
+                            .map_err(|error| bad_argument(&function, index, error))?;
+                    )*
+                    self($($arg),*).into_result()
+                })
+            }
+        }
+    };
+}
+
+native_fn!();
+native_fn!(A);
+native_fn!(A, B);
+native_fn!(A, B, C);
+native_fn!(A, B, C, D);
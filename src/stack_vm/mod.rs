@@ -1,13 +1,19 @@
+mod builtins;
+mod convert;
 mod runtime_error;
 mod stack;
 mod value;
 
 use super::*;
+use builtins::builtins;
 use value::*;
 
+pub use convert::{FromValue, IntoArgs, IntoNative, IntoValue, NativeResult};
 pub use runtime_error::*;
 pub use stack::VM;
-pub use value::Value;
+pub use value::{
+    Capture, EnumType, FunctionObj, NativeFn, Pointer, StructType, Value, VariantType,
+};
 
 #[cfg(test)]
 mod tests {
@@ -15,17 +21,17 @@ mod tests {
 
     #[test]
     fn test_simple_run() {
-        let mut chunk = Chunk::new();
-        let c = chunk.add_constant(Value::Number(1.2));
+        let mut chunk = Chunk::default();
+        let c = chunk.add_constant(Value::Float(1.2)).unwrap();
         chunk.add_inst(Inst::Const(c));
         chunk.add_inst(Inst::Neg);
         chunk.add_inst(Inst::Ret);
-        let mut vm = VM::new(&chunk);
+        let mut vm = VM::new();
 
-        match vm.run() {
-            Ok(results) => {
-                let Value::Number(v) = results[0] else {
-                    panic!("Expected a number, got {:?}", results[0]);
+        match vm.run(chunk) {
+            Ok(result) => {
+                let Value::Float(v) = result else {
+                    panic!("Expected a number, got {:?}", result);
                 };
                 assert_eq!(v, -1.2)
             }
@@ -35,10 +41,10 @@ mod tests {
 
     #[test]
     fn test_simple_expression() {
-        let mut chunk = Chunk::new();
-        let a = chunk.add_constant(Value::Number(1.2));
-        let b = chunk.add_constant(Value::Number(3.4));
-        let c = chunk.add_constant(Value::Number(5.6));
+        let mut chunk = Chunk::default();
+        let a = chunk.add_constant(Value::Float(1.2)).unwrap();
+        let b = chunk.add_constant(Value::Float(3.4)).unwrap();
+        let c = chunk.add_constant(Value::Float(5.6)).unwrap();
         chunk.add_inst(Inst::Const(a));
         chunk.add_inst(Inst::Const(b));
         chunk.add_inst(Inst::Add);
@@ -46,13 +52,13 @@ mod tests {
         chunk.add_inst(Inst::Div);
         chunk.add_inst(Inst::Neg);
         chunk.add_inst(Inst::Ret);
-        let mut vm = VM::new(&chunk);
-        match vm.run() {
-            Ok(results) => {
-                let Value::Number(v) = results[0] else {
-                    panic!("Expected a number, got {:?}", results[0]);
+        let mut vm = VM::new();
+        match vm.run(chunk) {
+            Ok(result) => {
+                let Value::Float(v) = result else {
+                    panic!("Expected a number, got {:?}", result);
                 };
-                assert_eq!(v, -0.82142866)
+                assert_eq!(v, -(1.2 + 3.4) / 5.6)
             }
             _ => unreachable!("Test fail: Didn't return a number"),
         }
@@ -1,10 +1,38 @@
 use std::fmt::Display;
 
+use super::*;
+
+// frames shown at each end of a long backtrace
+const TRACE_EDGE: usize = 10;
+
+#[derive(Debug)]
 pub enum RuntimeError {
     TypeMismatch(String),
     StackUnderFlow,
+    StackOverflow,
     BadInstruction(String),
     UndefinedVariable(String),
+    IntegerOverflow(String),
+    DivisionByZero,
+    IndexOutOfBounds {
+        index: i64,
+        len: usize,
+    },
+    NotCallable(String),
+    ArityMismatch {
+        expected: usize,
+        found: usize,
+    },
+    /// An argument of a native function has the wrong type. `index` starts at 1.
+    BadArgument {
+        function: String,
+        index: usize,
+        msg: String,
+    },
+    /// No arm of a `match` covers the value.
+    NoMatch(String),
+    /// Raised by a native function.
+    Native(String),
 }
 
 impl Display for RuntimeError {
@@ -12,8 +40,66 @@ impl Display for RuntimeError {
         match self {
             RuntimeError::TypeMismatch(msg) => write!(f, "Type mismatch: {}", msg),
             RuntimeError::StackUnderFlow => write!(f, "Stack is empty"),
+            RuntimeError::StackOverflow => write!(f, "Too many nested calls"),
             RuntimeError::BadInstruction(msg) => write!(f, "Instruction Invalid: {}", msg),
             RuntimeError::UndefinedVariable(msg) => write!(f, "Undefined variable: {}", msg),
+            RuntimeError::IntegerOverflow(msg) => write!(f, "Integer overflow: {}", msg),
+            RuntimeError::DivisionByZero => write!(f, "Division by zero"),
+            RuntimeError::IndexOutOfBounds { index, len } => {
+                write!(f, "Index {} out of bounds for length {}", index, len)
+            }
+            RuntimeError::NotCallable(msg) => write!(f, "Not callable: {}", msg),
+            RuntimeError::ArityMismatch { expected, found } => {
+                write!(f, "Expected {} arguments but got {}", expected, found)
+            }
+            RuntimeError::BadArgument {
+                function,
+                index,
+                msg,
+            } => write!(f, "Bad argument {} to {}: {}", index, function, msg),
+            RuntimeError::NoMatch(value) => write!(f, "No arm of the match covers {}", value),
+            RuntimeError::Native(msg) => write!(f, "{}", msg),
         }
     }
 }
+
+impl std::error::Error for RuntimeError {}
+
+/// A runtime error with the call frames active when it was raised, innermost first.
+#[derive(Debug)]
+pub struct Traceback {
+    pub error: RuntimeError,
+    pub frames: Vec<TraceFrame>,
+}
+
+#[derive(Debug)]
+pub struct TraceFrame {
+    pub function: String,
+    pub position: Position,
+}
+
+impl Traceback {
+    /// Renders the error and one `at file:line:col in function` line per frame.
+    /// The middle of deep backtraces is elided.
+    pub fn render(&self, path: Option<&str>) -> String {
+        let path = path.unwrap_or("<input>");
+        let mut out = format!("error: {}", self.error);
+        let count = self.frames.len();
+        for (i, frame) in self.frames.iter().enumerate() {
+            if count > 2 * TRACE_EDGE && i >= TRACE_EDGE && i < count - TRACE_EDGE {
+                if i == TRACE_EDGE {
+                    out += &format!("\n  ... {} more frames", count - 2 * TRACE_EDGE);
+                }
+                continue;
+            }
+            out += &format!("\n  at {}:{} in {}", path, frame.position, frame.function);
+        }
+        out
+    }
+}
+
+impl Display for Traceback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.render(None))
+    }
+}
@@ -1,41 +1,320 @@
-use std::collections::HashMap;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
 use super::*;
 
-pub struct VM<'a> {
-    stack: Vec<Value>,
+const FRAMES_MAX: usize = 1024;
+
+struct CallFrame {
+    closure: Rc<Closure>,
     pc: Pointer,
-    code: &'a Chunk,
-    globals: HashMap<String, Value>,
+    // stack index of the first argument, the callee sits right below it
+    base: usize,
+}
+
+pub struct VM {
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    globals: HashMap<Rc<str>, Value>,
+    // upvalues still pointing into the stack, shared by every closure capturing the same slot
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    // print every instruction and the stack after it
+    trace: bool,
 }
 
-impl<'a> VM<'a> {
-    pub fn new(code: &'a Chunk) -> Self {
+impl Default for VM {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VM {
+    pub fn new() -> Self {
+        let globals = builtins()
+            .into_iter()
+            .map(|native| (native.name.as_str().into(), Value::Native(Rc::new(native))))
+            .collect();
         VM {
             stack: Vec::new(),
-            pc: 0,
-            code,
-            globals: HashMap::new(),
+            frames: Vec::new(),
+            globals,
+            open_upvalues: Vec::new(),
+            trace: false,
         }
     }
 
-    pub fn run(&mut self) -> Result<Vec<Value>, RuntimeError> {
-        while self.pc < self.code.len() {
-            let op = &self.code[self.pc];
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+
+    pub fn run(&mut self, code: Chunk) -> Result<Value, Traceback> {
+        let script = FunctionObj {
+            name: "script".to_string(),
+            arity: 0,
+            chunk: code,
+            upvalues: Vec::new(),
+        };
+        self.reset();
+        self.frames.push(CallFrame {
+            closure: Rc::new(Closure {
+                function: Rc::new(script),
+                upvalues: Vec::new(),
+            }),
+            pc: 0,
+            base: 0,
+        });
+        self.execute().map_err(|error| self.traceback(error))
+    }
+
+    /// Calls `callee` with `args` from the host, outside of any script.
+    pub fn call(&mut self, callee: Value, args: Vec<Value>) -> Result<Value, Traceback> {
+        self.reset();
+        let argc = args.len();
+        self.stack.push(callee);
+        self.stack.extend(args);
+        let result = self
+            .call_value(argc)
+            .and_then(|()| match self.frames.is_empty() {
+                // natives are done once called
+                true => self.pop(),
+                false => self.execute(),
+            });
+        result.map_err(|error| self.traceback(error))
+    }
+
+    // drops what a failed run left behind, the globals stay
+    fn reset(&mut self) {
+        self.stack.clear();
+        self.frames.clear();
+        self.open_upvalues.clear();
+    }
+
+    fn execute(&mut self) -> Result<Value, RuntimeError> {
+        loop {
+            let frame = self.frames.last_mut().unwrap();
+            let closure = frame.closure.clone();
+            let function = &closure.function;
+            if frame.pc >= function.chunk.len() {
+                return Err(RuntimeError::BadInstruction(format!(
+                    "Run out of code in {}",
+                    function
+                )));
+            }
+            let op = function.chunk[frame.pc];
+            frame.pc += 1;
             match op {
                 Inst::Const(ind) => {
-                    self.stack.push(self.code.get_const(*ind as usize).clone());
+                    self.stack
+                        .push(function.chunk.get_const(ind as usize).clone());
                 }
                 Inst::Ret => {
-                    return Ok(self.stack.clone());
+                    let result = self.pop()?;
+                    let frame = self.frames.pop().unwrap();
+                    self.close_upvalues(frame.base);
+                    if self.frames.is_empty() {
+                        return Ok(result);
+                    }
+                    self.stack.truncate(frame.base - 1);
+                    self.stack.push(result);
                 }
                 Inst::Neg => match self.pop()? {
-                    Value::Number(v) => self.stack.push(Value::Number(-v)),
-                    _ => todo!("Invalid negation"),
+                    Value::Int(v) => match v.checked_neg() {
+                        Some(v) => self.stack.push(Value::Int(v)),
+                        None => return Err(RuntimeError::IntegerOverflow(format!("-({})", v))),
+                    },
+                    Value::Float(v) => self.stack.push(Value::Float(-v)),
+                    _ => return Err(RuntimeError::TypeMismatch("Expect Number".to_string())),
+                },
+                Inst::Not => match self.pop()? {
+                    Value::Bool(b) => self.stack.push(Value::Bool(!b)),
+                    _ => return Err(RuntimeError::TypeMismatch("Expect Bool".to_string())),
+                },
+                Inst::Eq | Inst::Ne => {
+                    let b = self.pop()?;
+                    let a = self.pop()?;
+                    self.stack
+                        .push(Value::Bool(values_equal(&a, &b) == (op == Inst::Eq)));
+                }
+                Inst::Add
+                | Inst::Sub
+                | Inst::Div
+                | Inst::Mul
+                | Inst::Lt
+                | Inst::Le
+                | Inst::Gt
+                | Inst::Ge => binary_op(self, &op)?,
+                Inst::Index => {
+                    let index = self.pop()?;
+                    let target = self.pop()?;
+                    self.stack.push(index_value(&target, &index)?);
+                }
+                Inst::SetIndex => {
+                    let value = self.pop()?;
+                    let index = self.pop()?;
+                    let target = self.pop()?;
+                    set_index(&target, &index, value)?;
+                }
+                Inst::Array(n) => {
+                    let from = self.stack.len() - n as usize;
+                    let elements = self.stack.split_off(from);
+                    self.stack.push(Value::array(elements));
+                }
+                Inst::Tuple(n) => {
+                    let from = self.stack.len() - n as usize;
+                    let elements = self.stack.split_off(from);
+                    self.stack.push(Value::Tuple(elements.into()));
+                }
+                Inst::TupleField(i) => match self.pop()? {
+                    Value::Tuple(elements) => match elements.get(i as usize) {
+                        Some(element) => self.stack.push(element.clone()),
+                        None => {
+                            return Err(RuntimeError::TypeMismatch(format!(
+                                "Tuple of {} elements has no field {}",
+                                elements.len(),
+                                i
+                            )))
+                        }
+                    },
+                    v => {
+                        return Err(RuntimeError::TypeMismatch(format!(
+                            "Expect a tuple, found {}",
+                            v
+                        )))
+                    }
+                },
+                Inst::Struct(ind) => {
+                    let Value::StructType(ty) = function.chunk.get_const(ind as usize).clone()
+                    else {
+                        return Err(RuntimeError::BadInstruction(
+                            "Build a struct, but not get its type!!".to_string(),
+                        ));
+                    };
+                    let from = self.stack.len() - ty.fields.len();
+                    let fields = RefCell::new(self.stack.split_off(from));
+                    self.stack
+                        .push(Value::Struct(Rc::new(StructObj { ty, fields })));
+                }
+                Inst::GetField(offset, ind) => {
+                    let object = self.pop()?;
+                    let name = function.chunk.get_const(ind as usize);
+                    let (object, offset) = struct_field(object, offset, name)?;
+                    let value = object.fields.borrow()[offset].clone();
+                    self.stack.push(value);
+                }
+                Inst::SetField(offset, ind) => {
+                    let value = self.pop()?;
+                    let object = self.pop()?;
+                    let name = function.chunk.get_const(ind as usize);
+                    let (object, offset) = struct_field(object, offset, name)?;
+                    object.fields.borrow_mut()[offset] = value;
+                }
+                Inst::Variant(ind, tag) => {
+                    let Value::EnumType(ty) = function.chunk.get_const(ind as usize).clone() else {
+                        return Err(RuntimeError::BadInstruction(
+                            "Build a variant, but not get its enum!!".to_string(),
+                        ));
+                    };
+                    let tag = tag as usize;
+                    let from = self.stack.len() - ty.variants[tag].fields.len();
+                    let values = self.stack.split_off(from);
+                    self.stack
+                        .push(Value::Variant(Rc::new(VariantObj { ty, tag, values })));
+                }
+                Inst::IsVariant(ind, tag) => {
+                    let ty = function.chunk.get_const(ind as usize);
+                    let is = match (self.pop()?, ty) {
+                        (Value::Variant(v), Value::EnumType(ty)) => {
+                            Rc::ptr_eq(&v.ty, ty) && v.tag == tag as usize
+                        }
+                        _ => false,
+                    };
+                    self.stack.push(Value::Bool(is));
+                }
+                Inst::VariantField(i) => match self.pop()? {
+                    Value::Variant(v) if (i as usize) < v.values.len() => {
+                        self.stack.push(v.values[i as usize].clone())
+                    }
+                    v => {
+                        return Err(RuntimeError::BadInstruction(format!(
+                            "Take value {} of {}!!",
+                            i, v
+                        )))
+                    }
                 },
-                Inst::Add | Inst::Sub | Inst::Div | Inst::Mul => binary_op(self, op)?,
+                Inst::NoMatch => return Err(RuntimeError::NoMatch(self.pop()?.to_string())),
+                Inst::Unpack(n) => match self.pop()? {
+                    Value::Tuple(elements) if elements.len() == n as usize => {
+                        self.stack.extend(elements.iter().cloned());
+                    }
+                    v => {
+                        return Err(RuntimeError::TypeMismatch(format!(
+                            "Expect a tuple of {} elements, found {}",
+                            n, v
+                        )))
+                    }
+                },
+                Inst::Slice => {
+                    let end = self.pop()?;
+                    let start = self.pop()?;
+                    let target = self.pop()?;
+                    self.stack.push(slice_value(&target, &start, &end)?);
+                }
+                Inst::Pop => {
+                    self.pop()?;
+                }
+                Inst::Jump(p) | Inst::Loop(p) => self.frames.last_mut().unwrap().pc = p,
+                Inst::ForNext(p) => {
+                    let len = self.stack.len();
+                    let Value::Int(index) = self.stack[len - 1] else {
+                        return Err(RuntimeError::BadInstruction(
+                            "Iterate, but not get index!!".to_string(),
+                        ));
+                    };
+                    match iter_element(&self.stack[len - 2], index as usize)? {
+                        Some((element, next)) => {
+                            self.stack[len - 1] = Value::Int(next as i64);
+                            self.stack.push(element);
+                        }
+                        None => self.frames.last_mut().unwrap().pc = p,
+                    }
+                }
+                Inst::Drop(n) => {
+                    let from = self.stack.len() - n as usize;
+                    self.close_upvalues(from);
+                    self.stack.truncate(from);
+                }
+                Inst::Range => match (self.pop()?, self.pop()?) {
+                    (Value::Int(end), Value::Int(start)) => {
+                        self.stack.push(Value::Range(start, end))
+                    }
+                    _ => return Err(RuntimeError::TypeMismatch("Expect Int bounds".to_string())),
+                },
+                Inst::JumpIfFalse(p) => match self.peek(0)? {
+                    Value::Bool(false) => self.frames.last_mut().unwrap().pc = p,
+                    Value::Bool(true) => {}
+                    _ => return Err(RuntimeError::TypeMismatch("Expect Bool".to_string())),
+                },
+                Inst::JumpIfTrue(p) => match self.peek(0)? {
+                    Value::Bool(true) => self.frames.last_mut().unwrap().pc = p,
+                    Value::Bool(false) => {}
+                    _ => return Err(RuntimeError::TypeMismatch("Expect Bool".to_string())),
+                },
+                Inst::EndBlock(n) => {
+                    let value = self.pop()?;
+                    let from = self.stack.len() - n as usize;
+                    self.close_upvalues(from);
+                    self.stack.truncate(from);
+                    self.stack.push(value);
+                }
+                Inst::Get(slot) => {
+                    let base = self.frames.last().unwrap().base;
+                    self.stack.push(self.stack[base + slot as usize].clone());
+                }
+                Inst::Set(slot) => {
+                    let base = self.frames.last().unwrap().base;
+                    self.stack[base + slot as usize] = self.pop()?;
+                }
                 Inst::DefineGlobal(ind) => {
-                    let Value::String(name) = self.code.get_const(*ind as usize) else {
+                    let Value::String(name) = function.chunk.get_const(ind as usize) else {
                         return Err(RuntimeError::BadInstruction(
                             "Unwarp Ident, but not get string!!".to_string(),
                         ));
@@ -44,55 +323,390 @@ impl<'a> VM<'a> {
                     self.globals.insert(name.clone(), value);
                 }
                 Inst::GetGlobal(ind) => {
-                    let Value::String(name) = self.code.get_const(*ind as usize) else {
+                    let Value::String(name) = function.chunk.get_const(ind as usize) else {
                         return Err(RuntimeError::BadInstruction(
                             "Unwrap Ident, but not get string!!".to_string(),
                         ));
                     };
-                    if let Some(value) = self.globals.get(name) {
+                    if let Some(value) = self.globals.get(&**name) {
                         self.stack.push(value.clone());
                     } else {
                         return Err(RuntimeError::UndefinedVariable(name.to_string()));
                     }
                 }
+                Inst::GetUpvalue(ind) => {
+                    let value = match &*closure.upvalues[ind as usize].borrow() {
+                        Upvalue::Open(slot) => self.stack[*slot].clone(),
+                        Upvalue::Closed(value) => value.clone(),
+                    };
+                    self.stack.push(value);
+                }
+                Inst::Closure(ind) => {
+                    let Value::Function(function) = function.chunk.get_const(ind as usize) else {
+                        return Err(RuntimeError::BadInstruction(
+                            "Make closure, but not get function!!".to_string(),
+                        ));
+                    };
+                    let base = self.frames.last().unwrap().base;
+                    let upvalues = function
+                        .upvalues
+                        .iter()
+                        .map(|capture| match capture.is_local {
+                            true => self.capture_upvalue(base + capture.index as usize),
+                            false => closure.upvalues[capture.index as usize].clone(),
+                        })
+                        .collect();
+                    self.stack.push(Value::Closure(Rc::new(Closure {
+                        function: function.clone(),
+                        upvalues,
+                    })));
+                }
+                Inst::Call(argc) => self.call_value(argc as usize)?,
+            }
+            if self.trace {
+                self.print_stack(&op, &function.chunk);
             }
-            self.pc += 1;
-            self.print_stack(op, self.code);
         }
-        Ok(self.stack.clone())
     }
 
-    pub fn print_stack(&self, op: &Inst, chunk: &Chunk) {
+    /// Attaches the frames active when `error` was raised, innermost first.
+    fn traceback(&self, error: RuntimeError) -> Traceback {
+        let frames = self
+            .frames
+            .iter()
+            .rev()
+            .map(|frame| {
+                let function = &frame.closure.function;
+                TraceFrame {
+                    function: function.name.clone(),
+                    // the pc is already past the instruction being run
+                    position: function.chunk.position(frame.pc.saturating_sub(1)),
+                }
+            })
+            .collect();
+        Traceback { error, frames }
+    }
+
+    pub fn globals(&self) -> &HashMap<Rc<str>, Value> {
+        &self.globals
+    }
+
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.globals.insert(name.into(), value);
+    }
+
+    fn print_stack(&self, op: &Inst, chunk: &Chunk) {
         print!("{:30} ", op.disassemble(chunk));
         for value in &self.stack {
-            match value {
-                Value::Number(n) => print!("[{}]", n),
-                Value::String(s) => print!("[{:?}]", s),
-                Value::Unit => print!("[()]"),
-            }
+            print!("[{}]", value);
         }
         println!();
     }
 
+    fn call_value(&mut self, argc: usize) -> Result<(), RuntimeError> {
+        let closure = match self.peek(argc)? {
+            Value::Native(native) => return self.call_native(native.clone(), argc),
+            Value::Closure(closure) => closure.clone(),
+            Value::Function(function) => Rc::new(Closure {
+                function: function.clone(),
+                upvalues: Vec::new(),
+            }),
+            callee => return Err(RuntimeError::NotCallable(callee.to_string())),
+        };
+        if closure.function.arity != argc {
+            return Err(RuntimeError::ArityMismatch {
+                expected: closure.function.arity,
+                found: argc,
+            });
+        }
+        if self.frames.len() >= FRAMES_MAX {
+            return Err(RuntimeError::StackOverflow);
+        }
+        self.frames.push(CallFrame {
+            closure,
+            pc: 0,
+            base: self.stack.len() - argc,
+        });
+        Ok(())
+    }
+
+    fn call_native(&mut self, native: Rc<NativeFn>, argc: usize) -> Result<(), RuntimeError> {
+        if argc != native.arity && !(native.variadic && argc > native.arity) {
+            return Err(RuntimeError::ArityMismatch {
+                expected: native.arity,
+                found: argc,
+            });
+        }
+        let args = self.stack.len() - argc;
+        let result = (native.func)(&self.stack[args..])?;
+        // drop the arguments and the callee
+        self.stack.truncate(args - 1);
+        self.stack.push(result);
+        Ok(())
+    }
+
+    fn capture_upvalue(&mut self, slot: usize) -> Rc<RefCell<Upvalue>> {
+        let existing = self
+            .open_upvalues
+            .iter()
+            .find(|upvalue| matches!(*upvalue.borrow(), Upvalue::Open(s) if s == slot));
+        if let Some(upvalue) = existing {
+            return upvalue.clone();
+        }
+        let upvalue = Rc::new(RefCell::new(Upvalue::Open(slot)));
+        self.open_upvalues.push(upvalue.clone());
+        upvalue
+    }
+
+    /// Moves the values of every upvalue pointing at or above `from` off the stack.
+    fn close_upvalues(&mut self, from: usize) {
+        let stack = &self.stack;
+        self.open_upvalues.retain(|upvalue| {
+            let mut upvalue = upvalue.borrow_mut();
+            match *upvalue {
+                Upvalue::Open(slot) if slot >= from => {
+                    *upvalue = Upvalue::Closed(stack[slot].clone());
+                    false
+                }
+                _ => true,
+            }
+        });
+    }
+
+    fn peek(&self, distance: usize) -> Result<&Value, RuntimeError> {
+        self.stack
+            .len()
+            .checked_sub(distance + 1)
+            .map(|ind| &self.stack[ind])
+            .ok_or(RuntimeError::StackUnderFlow)
+    }
+
     fn pop(&mut self) -> Result<Value, RuntimeError> {
         self.stack.pop().ok_or(RuntimeError::StackUnderFlow)
     }
 }
 
-fn binary_op(vm: &mut VM, op: &Inst) -> Result<(), RuntimeError> {
-    let Value::Number(b) = vm.pop()? else {
-        return Err(RuntimeError::TypeMismatch("Expect Number".to_string()));
+/// The struct and where its field `name` is, expected at `offset`.
+fn struct_field(
+    object: Value,
+    offset: u8,
+    name: &Value,
+) -> Result<(Rc<StructObj>, usize), RuntimeError> {
+    let Value::String(name) = name else {
+        return Err(RuntimeError::BadInstruction(
+            "Access a field, but not get its name!!".to_string(),
+        ));
     };
-    let Value::Number(a) = vm.pop()? else {
-        return Err(RuntimeError::TypeMismatch("Expect Number".to_string()));
+    let Value::Struct(object) = object else {
+        return Err(RuntimeError::TypeMismatch(format!(
+            "Expect a struct with a field `{}`, found {}",
+            name, object
+        )));
     };
-    let v = match op {
-        Inst::Add => Value::Number(a + b),
-        Inst::Sub => Value::Number(a - b),
-        Inst::Mul => Value::Number(a * b),
-        Inst::Div => Value::Number(a / b),
-        _ => panic!("Invalid binary operation"),
+    match object.ty.offset(name, offset as usize) {
+        Some(offset) => Ok((object, offset)),
+        None => Err(RuntimeError::TypeMismatch(format!(
+            "Struct `{}` has no field `{}`",
+            object.ty.name, name
+        ))),
+    }
+}
+
+/// Equality of the language: numbers compare by value, whatever their type.
+fn values_equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Int(a), Value::Float(b)) | (Value::Float(b), Value::Int(a)) => *a as f64 == *b,
+        (Value::Float(a), Value::Float(b)) => a == b,
+        (Value::Tuple(a), Value::Tuple(b)) => elements_equal(a, b),
+        (Value::Array(a), Value::Array(b)) => {
+            Rc::ptr_eq(a, b) || elements_equal(&a.borrow(), &b.borrow())
+        }
+        (Value::Variant(a), Value::Variant(b)) => {
+            Rc::ptr_eq(&a.ty, &b.ty) && a.tag == b.tag && elements_equal(&a.values, &b.values)
+        }
+        (Value::Struct(a), Value::Struct(b)) => {
+            Rc::ptr_eq(a, b)
+                || (Rc::ptr_eq(&a.ty, &b.ty)
+                    && elements_equal(&a.fields.borrow(), &b.fields.borrow()))
+        }
+        (a, b) => a == b,
+    }
+}
+
+fn elements_equal(a: &[Value], b: &[Value]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| values_equal(a, b))
+}
+
+/// Arithmetic and ordering on numbers. Two ints stay ints, checked for
+/// overflow; as soon as a float is involved, both sides are promoted to floats.
+fn binary_op(vm: &mut VM, op: &Inst) -> Result<(), RuntimeError> {
+    let b = vm.pop()?;
+    let a = vm.pop()?;
+    let v = match (a, b) {
+        (Value::Int(a), Value::Int(b)) => int_op(a, b, op)?,
+        (Value::Int(a), Value::Float(b)) => float_op(a as f64, b, op),
+        (Value::Float(a), Value::Int(b)) => float_op(a, b as f64, op),
+        (Value::Float(a), Value::Float(b)) => float_op(a, b, op),
+        (Value::String(a), Value::String(b)) => string_op(&a, &b, op)?,
+        _ => return Err(RuntimeError::TypeMismatch("Expect Number".to_string())),
     };
     vm.stack.push(v);
     Ok(())
 }
+
+/// Concatenation, and ordering by code points.
+fn string_op(a: &str, b: &str, op: &Inst) -> Result<Value, RuntimeError> {
+    match op {
+        Inst::Add => Ok(Value::String([a, b].concat().into())),
+        Inst::Lt => Ok(Value::Bool(a < b)),
+        Inst::Le => Ok(Value::Bool(a <= b)),
+        Inst::Gt => Ok(Value::Bool(a > b)),
+        Inst::Ge => Ok(Value::Bool(a >= b)),
+        _ => Err(RuntimeError::TypeMismatch(format!(
+            "Cannot apply {:?} to strings",
+            op
+        ))),
+    }
+}
+
+/// The element a `for` loop gets at `index` and the index of the next one,
+/// `None` once past the end. Strings are walked by byte offsets, so that each
+/// character is found without going through the ones before it.
+fn iter_element(iterable: &Value, index: usize) -> Result<Option<(Value, usize)>, RuntimeError> {
+    let element = match iterable {
+        Value::Range(start, end) => start
+            .checked_add(index as i64)
+            .filter(|i| i < end)
+            .map(Value::Int),
+        Value::Array(array) => array.borrow().get(index).cloned(),
+        Value::String(s) => {
+            let element = s[index..].chars().next().map(|c| {
+                let next = index + c.len_utf8();
+                (Value::String(c.to_string().into()), next)
+            });
+            return Ok(element);
+        }
+        v => {
+            return Err(RuntimeError::TypeMismatch(format!(
+                "Cannot iterate over {}",
+                v
+            )))
+        }
+    };
+    Ok(element.map(|element| (element, index + 1)))
+}
+
+/// The element at `index`. Strings are indexed by characters, not bytes.
+fn index_value(target: &Value, index: &Value) -> Result<Value, RuntimeError> {
+    match target {
+        Value::String(s) => {
+            let i = checked_index(index, s.chars().count())?;
+            let c = s.chars().nth(i).unwrap();
+            Ok(Value::String(c.to_string().into()))
+        }
+        Value::Array(array) => {
+            let array = array.borrow();
+            let i = checked_index(index, array.len())?;
+            Ok(array[i].clone())
+        }
+        v => Err(RuntimeError::TypeMismatch(format!("Cannot index {}", v))),
+    }
+}
+
+fn set_index(target: &Value, index: &Value, value: Value) -> Result<(), RuntimeError> {
+    match target {
+        Value::Array(array) => {
+            let mut array = array.borrow_mut();
+            let i = checked_index(index, array.len())?;
+            array[i] = value;
+            Ok(())
+        }
+        v => Err(RuntimeError::TypeMismatch(format!(
+            "Cannot assign to an element of {}",
+            v
+        ))),
+    }
+}
+
+/// An index into something of length `len`.
+fn checked_index(index: &Value, len: usize) -> Result<usize, RuntimeError> {
+    let Value::Int(i) = *index else {
+        return Err(RuntimeError::TypeMismatch(format!(
+            "Expect an int index, found {}",
+            index
+        )));
+    };
+    usize::try_from(i)
+        .ok()
+        .filter(|i| *i < len)
+        .ok_or(RuntimeError::IndexOutOfBounds { index: i, len })
+}
+
+/// A copy of part of a string or an array.
+fn slice_value(target: &Value, start: &Value, end: &Value) -> Result<Value, RuntimeError> {
+    match target {
+        Value::String(s) => {
+            let (start, end) = slice_bounds(start, end, s.chars().count())?;
+            let slice: String = s.chars().skip(start).take(end - start).collect();
+            Ok(Value::String(slice.into()))
+        }
+        Value::Array(array) => {
+            let array = array.borrow();
+            let (start, end) = slice_bounds(start, end, array.len())?;
+            Ok(Value::array(array[start..end].to_vec()))
+        }
+        v => Err(RuntimeError::TypeMismatch(format!("Cannot slice {}", v))),
+    }
+}
+
+/// The bounds of a slice of something of length `len`. A left out bound is unit
+/// and stands for the start or the end. The slice is empty when they cross.
+fn slice_bounds(start: &Value, end: &Value, len: usize) -> Result<(usize, usize), RuntimeError> {
+    let bound = |bound: &Value, default: usize| match *bound {
+        Value::Unit => Ok(default),
+        Value::Int(i) => usize::try_from(i)
+            .ok()
+            .filter(|i| *i <= len)
+            .ok_or(RuntimeError::IndexOutOfBounds { index: i, len }),
+        ref v => Err(RuntimeError::TypeMismatch(format!(
+            "Expect an int bound, found {}",
+            v
+        ))),
+    };
+    let start = bound(start, 0)?;
+    let end = bound(end, len)?;
+    Ok((start, end.max(start)))
+}
+
+fn int_op(a: i64, b: i64, op: &Inst) -> Result<Value, RuntimeError> {
+    let checked = |v: Option<i64>| {
+        v.map(Value::Int)
+            .ok_or_else(|| RuntimeError::IntegerOverflow(format!("{:?} of {} and {}", op, a, b)))
+    };
+    match op {
+        Inst::Add => checked(a.checked_add(b)),
+        Inst::Sub => checked(a.checked_sub(b)),
+        Inst::Mul => checked(a.checked_mul(b)),
+        Inst::Div if b == 0 => Err(RuntimeError::DivisionByZero),
+        Inst::Div => checked(a.checked_div(b)),
+        Inst::Lt => Ok(Value::Bool(a < b)),
+        Inst::Le => Ok(Value::Bool(a <= b)),
+        Inst::Gt => Ok(Value::Bool(a > b)),
+        Inst::Ge => Ok(Value::Bool(a >= b)),
+        _ => panic!("Invalid binary operation"),
+    }
+}
+
+fn float_op(a: f64, b: f64, op: &Inst) -> Value {
+    match op {
+        Inst::Add => Value::Float(a + b),
+        Inst::Sub => Value::Float(a - b),
+        Inst::Mul => Value::Float(a * b),
+        Inst::Div => Value::Float(a / b),
+        Inst::Lt => Value::Bool(a < b),
+        Inst::Le => Value::Bool(a <= b),
+        Inst::Gt => Value::Bool(a > b),
+        Inst::Ge => Value::Bool(a >= b),
+        _ => panic!("Invalid binary operation"),
+    }
+}
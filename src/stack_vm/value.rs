@@ -1,20 +1,243 @@
-use std::fmt::Display;
+use std::{cell::RefCell, fmt::Display, rc::Rc};
 
-#[derive(Clone, Debug, PartialEq)]
+use super::*;
+
+#[derive(Clone, Debug)]
 pub enum Value {
-    Number(f32),
-    String(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    String(Rc<str>),
+    Tuple(Rc<[Value]>),
+    /// `start..end`, up to but not including `end`.
+    Range(i64, i64),
+    /// Shared: every copy of an array sees the changes made through the others.
+    Array(Rc<RefCell<Vec<Value>>>),
+    /// Shared and mutable, like arrays.
+    Struct(Rc<StructObj>),
+    /// The layout a struct literal is built with, only found among constants.
+    StructType(Rc<StructType>),
+    /// Immutable, like tuples.
+    Variant(Rc<VariantObj>),
+    /// The enum a variant is built or tested with, only found among constants.
+    EnumType(Rc<EnumType>),
+    Function(Rc<FunctionObj>),
+    Closure(Rc<Closure>),
+    Native(Rc<NativeFn>),
     Unit,
 }
 
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Int(a), Value::Int(b)) => a == b,
+            // bitwise, so that `0.0` and `-0.0` stay distinct constants
+            (Value::Float(a), Value::Float(b)) => a.to_bits() == b.to_bits(),
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            // interned strings are compared by address first
+            (Value::String(a), Value::String(b)) => Rc::ptr_eq(a, b) || a == b,
+            (Value::Tuple(a), Value::Tuple(b)) => a == b,
+            (Value::Range(a, b), Value::Range(c, d)) => (a, b) == (c, d),
+            (Value::Array(a), Value::Array(b)) => Rc::ptr_eq(a, b) || a == b,
+            (Value::Struct(a), Value::Struct(b)) => {
+                Rc::ptr_eq(a, b) || (Rc::ptr_eq(&a.ty, &b.ty) && a.fields == b.fields)
+            }
+            (Value::StructType(a), Value::StructType(b)) => Rc::ptr_eq(a, b),
+            (Value::Variant(a), Value::Variant(b)) => {
+                Rc::ptr_eq(&a.ty, &b.ty) && a.tag == b.tag && a.values == b.values
+            }
+            (Value::EnumType(a), Value::EnumType(b)) => Rc::ptr_eq(a, b),
+            // functions are only equal to themselves
+            (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
+            (Value::Closure(a), Value::Closure(b)) => Rc::ptr_eq(a, b),
+            (Value::Native(a), Value::Native(b)) => Rc::ptr_eq(a, b),
+            (Value::Unit, Value::Unit) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Value {
+    pub fn array(elements: Vec<Value>) -> Self {
+        Value::Array(Rc::new(RefCell::new(elements)))
+    }
+}
+
 impl Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Value::Number(n) => write!(f, "{}", n),
+            Value::Int(n) => write!(f, "{}", n),
+            Value::Float(n) => write!(f, "{:?}", n),
+            Value::Bool(b) => write!(f, "{}", b),
             Value::String(s) => write!(f, "{:?}", s),
+            Value::Array(elements) => {
+                let elements: Vec<_> = elements.borrow().iter().map(|e| e.to_string()).collect();
+                write!(f, "[{}]", elements.join(", "))
+            }
+            Value::Range(start, end) => write!(f, "{}..{}", start, end),
+            Value::Struct(object) => {
+                let values = object.fields.borrow();
+                let fields: Vec<_> = (object.ty.fields.iter().zip(values.iter()))
+                    .map(|(name, value)| format!("{}: {}", name, value))
+                    .collect();
+                match fields.len() {
+                    0 => write!(f, "{} {{}}", object.ty.name),
+                    _ => write!(f, "{} {{ {} }}", object.ty.name, fields.join(", ")),
+                }
+            }
+            Value::StructType(ty) => write!(f, "<struct {}>", ty.name),
+            Value::Variant(variant) => {
+                let name = &variant.ty.variants[variant.tag].name;
+                write!(f, "{}::{}", variant.ty.name, name)?;
+                if !variant.values.is_empty() {
+                    let values: Vec<_> = variant.values.iter().map(|v| v.to_string()).collect();
+                    write!(f, "({})", values.join(", "))?;
+                }
+                Ok(())
+            }
+            Value::EnumType(ty) => write!(f, "<enum {}>", ty.name),
+            Value::Tuple(elements) => {
+                let elements: Vec<_> = elements.iter().map(|e| e.to_string()).collect();
+                match elements.len() {
+                    1 => write!(f, "({},)", elements[0]),
+                    _ => write!(f, "({})", elements.join(", ")),
+                }
+            }
+            Value::Function(function) => write!(f, "{}", function),
+            Value::Closure(closure) => write!(f, "{}", closure.function),
+            Value::Native(native) => write!(f, "<native fn {}>", native.name),
             Value::Unit => write!(f, "()"),
         }
     }
 }
 
+/// The name and the fields of a declared struct, in their order of declaration.
+#[derive(Debug)]
+pub struct StructType {
+    pub name: String,
+    pub fields: Vec<Rc<str>>,
+}
+
+impl StructType {
+    /// Where `field` is. `hint` is where the compiler expects it, which is
+    /// checked first.
+    pub fn offset(&self, field: &Rc<str>, hint: usize) -> Option<usize> {
+        match self.fields.get(hint) {
+            Some(name) if Rc::ptr_eq(name, field) || name == field => Some(hint),
+            _ => self.fields.iter().position(|name| name == field),
+        }
+    }
+}
+
+/// An instance of a struct, with its fields in the order of its type.
+#[derive(Debug)]
+pub struct StructObj {
+    pub ty: Rc<StructType>,
+    pub fields: RefCell<Vec<Value>>,
+}
+
+/// The variants of a declared enum, in their order of declaration.
+#[derive(Debug)]
+pub struct EnumType {
+    pub name: String,
+    pub variants: Vec<VariantType>,
+}
+
+/// A variant with the names of the values it holds.
+#[derive(Debug)]
+pub struct VariantType {
+    pub name: String,
+    pub fields: Vec<String>,
+}
+
+/// A value of an enum: the variant at `tag`, with its values.
+#[derive(Debug)]
+pub struct VariantObj {
+    pub ty: Rc<EnumType>,
+    pub tag: usize,
+    pub values: Vec<Value>,
+}
+
+/// A compiled function: its own chunk of bytecode plus the number of parameters it takes.
+#[derive(Debug)]
+pub struct FunctionObj {
+    pub name: String,
+    pub arity: usize,
+    pub chunk: Chunk,
+    /// Where each upvalue of the closure comes from, in order.
+    pub upvalues: Vec<Capture>,
+}
+
+/// Describes one captured variable: either a local slot of the enclosing
+/// function (`is_local`), or one of the enclosing function's own upvalues.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Capture {
+    pub is_local: bool,
+    pub index: u16,
+}
+
+/// A function together with the variables it captured.
+#[derive(Debug)]
+pub struct Closure {
+    pub function: Rc<FunctionObj>,
+    pub upvalues: Vec<Rc<RefCell<Upvalue>>>,
+}
+
+/// A captured variable. It points into the stack while the declaring frame
+/// is alive, and owns the value once that frame returns.
+#[derive(Debug)]
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+impl Display for FunctionObj {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn {}>", self.name)
+    }
+}
+
+type NativeBody = dyn Fn(&[Value]) -> Result<Value, RuntimeError>;
+
+/// A function implemented in Rust. It is only called with `arity` arguments.
+pub struct NativeFn {
+    pub name: String,
+    pub arity: usize,
+    /// Takes `arity` arguments or more.
+    pub variadic: bool,
+    pub func: Box<NativeBody>,
+}
+
+impl NativeFn {
+    pub fn new(
+        name: &str,
+        arity: usize,
+        func: impl Fn(&[Value]) -> Result<Value, RuntimeError> + 'static,
+    ) -> Self {
+        NativeFn {
+            name: name.to_string(),
+            arity,
+            variadic: false,
+            func: Box::new(func),
+        }
+    }
+
+    pub fn variadic(
+        name: &str,
+        min_arity: usize,
+        func: impl Fn(&[Value]) -> Result<Value, RuntimeError> + 'static,
+    ) -> Self {
+        NativeFn {
+            variadic: true,
+            ..Self::new(name, min_arity, func)
+        }
+    }
+}
+
+impl std::fmt::Debug for NativeFn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<native fn {}/{}>", self.name, self.arity)
+    }
+}
+
 pub type Pointer = usize;